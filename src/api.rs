@@ -0,0 +1,198 @@
+use std::error::Error;
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+
+use crate::{Config, DefFile, Laststate, Specimen, TestDefinition, TestInfo, TestCategory, TestLossClass, read_last_state,
+    check_state, write_pause, write_test_loss, write_test_loss_end, registry};
+
+/// Error of API request with HTTP status code
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    fn invalid(message: String) -> ApiError {
+        ApiError { status: 400, message }
+    }
+}
+
+impl From<Box<dyn Error>> for ApiError {
+    fn from(e: Box<dyn Error>) -> Self {
+        ApiError { status: 500, message: e.to_string() }
+    }
+}
+
+/// Test definition, same values as inserted by `workflow::define_test`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DefinitionRequest {
+    pub tr_number: String,
+    /// Specimens separated by `;`, position after `@`
+    pub specimen_id: String,
+    pub request_type: String,
+    pub testing_category: String,
+    pub technician: String,
+    /// Values of custom definition fields
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Time loss classification, same values as chosen by `workflow::choose_time_loss`
+#[derive(Debug, Deserialize)]
+pub struct LossRequest {
+    pub classification: Vec<String>,
+    /// Free text comment of IN record
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiResponse {
+    pub status: String,
+    pub warnings: Vec<String>,
+}
+
+fn check_value(values: &[String], value: &str, name: &str) -> Result<(), ApiError> {
+    if values.iter().any(|v| v.trim() == value) {
+        Ok(())
+    } else {
+        Err(ApiError::invalid(format!("Unknown {}: {}, allowed: {}", name, value, values.join(","))))
+    }
+}
+
+/// Validate definition against config lists of stand and store it as pending definition
+/// written to log at next test start
+pub fn define_test(config: &Config, body: &str) -> Result<ApiResponse, ApiError> {
+    let request: DefinitionRequest = serde_json::from_str(body)
+        .map_err(|e| ApiError::invalid(format!("Invalid definition: {}", e)))?;
+    let specimens = Specimen::parse_list(&request.specimen_id);
+    if request.tr_number.trim().is_empty() || specimens.is_empty() {
+        return Err(ApiError::invalid("TR number and Specimen ID are required".to_string()));
+    }
+
+    let test_request = TestInfo::new(&config.get_config_file_path(&config.test_request_type_cfg))?;
+    check_value(&test_request.values, &request.request_type, "request type")?;
+    let operators = TestInfo::new(&config.get_config_file_path(&config.operator_list_cfg))?;
+    check_value(&operators.values, &request.technician, "technician")?;
+    let test_category = TestCategory::new(&config.get_config_file_path(&config.test_category_cfg))?;
+    let available_time = match test_category.values.get(request.testing_category.as_str()) {
+        Some(time) => time.to_owned(),
+        None => {
+            let categories: Vec<String> = test_category.values.keys().cloned().collect();
+            return Err(ApiError::invalid(format!("Unknown testing category: {}, allowed: {}",
+                request.testing_category, categories.join(","))));
+        },
+    };
+
+    if let Some(name) = request.fields.keys().find(|name| !config.definition_fields.iter().any(|f| &&f.name == name)) {
+        return Err(ApiError::invalid(format!("Unknown definition field: {}", name)));
+    }
+    let mut fields = BTreeMap::new();
+    for field in config.definition_fields.iter() {
+        let value = request.fields.get(&field.name).map_or("", |v| v.as_str());
+        fields.insert(field.name.to_owned(), field.check(value, config).map_err(ApiError::invalid)?);
+    }
+
+    let mut warnings = Vec::new();
+    let tr_registry = registry::TrRegistry::new(&config.get_config_file_path(&config.tr_registry_cfg))?;
+    match tr_registry.find(&request.tr_number) {
+        Some(entry) => {
+            if entry.is_closed() {
+                warnings.push(format!("TR {} is closed", entry.tr_number));
+            }
+            for specimen in specimens.iter().filter(|s| !entry.expects_specimen(&s.id)) {
+                warnings.push(format!("Specimen {} is not expected for TR {}", specimen.id, entry.tr_number));
+            }
+        },
+        None => warnings.push(format!("TR {} is not in TR registry", request.tr_number)),
+    }
+
+    let definition = TestDefinition {
+        tr_number: request.tr_number.trim().to_string(),
+        specimen_id: Specimen::format_list(&specimens),
+        request_type: request.request_type,
+        testing_category: request.testing_category,
+        technician: request.technician,
+        available_time,
+        fields,
+        ..Default::default()
+    };
+    DefFile::new(config).write_temp_output(&definition)?;
+
+    Ok(ApiResponse { status: "pending".to_string(), warnings })
+}
+
+/// Pending definition waiting for test start
+pub fn pending_definition(config: &Config) -> Result<DefinitionRequest, ApiError> {
+    let definition = match DefFile::new(config).read()? {
+        Some(pending) => pending.definition,
+        None => return Err(ApiError { status: 404, message: "No pending definition".to_string() }),
+    };
+    Ok(DefinitionRequest {
+        tr_number: definition.tr_number,
+        specimen_id: definition.specimen_id,
+        request_type: definition.request_type,
+        testing_category: definition.testing_category,
+        technician: definition.technician,
+        fields: definition.fields,
+    })
+}
+
+/// Validate classification against time loss config and write start of time loss.
+/// Running test has to be ended at the stand.
+pub fn record_loss(config: &Config, body: &str) -> Result<ApiResponse, ApiError> {
+    let request: LossRequest = serde_json::from_str(body)
+        .map_err(|e| ApiError::invalid(format!("Invalid time loss: {}", e)))?;
+    let class = request.classification;
+    let loss_class = TestLossClass::new(&config.get_config_file_path(&config.timeloss_classification_cfg))?;
+    let valid = class.len() == 3 && loss_class.values.get(&class[0])
+        .and_then(|second| second.get(&class[1]))
+        .is_some_and(|third| third.contains(&class[2]));
+    if !valid {
+        return Err(ApiError::invalid(format!("Unknown time loss classification: {}", class.join(" / "))));
+    }
+
+    let state = read_last_state(config)?;
+    match check_state(&state) {
+        Laststate::IN(vec_data) => {
+            if vec_data[0].contains("Test Start") {
+                return Err(ApiError { status: 409, message: "Test is running, end of test has to be confirmed at the stand".to_string() });
+            }
+            write_test_loss_end(config, &vec_data)?;
+        },
+        // Classification of paused test is reason of pause
+        Laststate::PAUSE(_) => {
+            write_pause(config, Some(class))?;
+            return Ok(ApiResponse { status: "recorded".to_string(), warnings: Vec::new() });
+        },
+        Laststate::OUT(_) | Laststate::EMPTY => (),
+    }
+    write_test_loss(config, class, request.comment.as_deref())?;
+
+    Ok(ApiResponse { status: "recorded".to_string(), warnings: Vec::new() })
+}
+
+/// Handle API request for stand, `None` if url is not API endpoint
+pub fn route(config: &Config, method: &str, path: &str, body: &str) -> Option<Result<String, ApiError>> {
+    let res = match (method, path) {
+        ("POST", "definition") => define_test(config, body).map(|r| serde_json::to_string(&r)),
+        ("GET", "definition") => pending_definition(config).map(|r| serde_json::to_string(&r)),
+        ("POST", "loss") => record_loss(config, body).map(|r| serde_json::to_string(&r)),
+        _ => return None,
+    };
+    Some(res.and_then(|json| json.map_err(|e| ApiError::from(Box::new(e) as Box<dyn Error>))))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_value() {
+        let values = vec!("text".to_string(), " other".to_string());
+        assert!(check_value(&values, "other", "technician").is_ok());
+        assert_eq!(check_value(&values, "nobody", "technician").unwrap_err().status, 400);
+    }
+}
@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::path::PathBuf;
+use chrono::NaiveDate;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::{Config, TestInfo, lang};
+
+/// Format of date field values
+pub const DATE_FORMAT: &str = "%d/%m/%Y";
+
+/// Type of custom definition field value
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldType {
+    #[default]
+    Text,
+    /// Value chosen from one line config file in config folder
+    List(PathBuf),
+    Number,
+    /// Date in format dd/mm/YYYY
+    Date,
+}
+
+/// Custom field of test definition configured per lab, written to log as `name::value`.
+/// Name must differ from keys of standard definition lines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DefinitionField {
+    pub name: String,
+    /// Text of prompt, name is used if empty
+    #[serde(default)]
+    pub label: String,
+    #[serde(default, rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    /// Regular expression whole value has to match
+    #[serde(default)]
+    pub validation: Option<String>,
+}
+
+impl DefinitionField {
+    pub fn label(&self) -> &str {
+        if self.label.is_empty() { &self.name } else { &self.label }
+    }
+
+    /// Values of list field
+    pub fn list_values(&self, config: &Config) -> Result<Vec<String>, Box<dyn Error>> {
+        match &self.field_type {
+            FieldType::List(cfg) => {
+                let info = TestInfo::new(&config.get_config_file_path(cfg))?;
+                Ok(info.values.iter().map(|v| v.trim().to_string()).collect())
+            },
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Check inserted value, returns trimmed value or reason why it is not valid
+    pub fn check(&self, value: &str, config: &Config) -> Result<String, String> {
+        let values = self.list_values(config).map_err(|e| e.to_string())?;
+        self.validate(value, &values)
+    }
+
+    /// Check value against type and validation, `values` are allowed values of list field
+    fn validate(&self, value: &str, values: &[String]) -> Result<String, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            if self.required {
+                return Err(lang::format("field_required", &[&self.label()]));
+            }
+            return Ok(String::new());
+        }
+        match &self.field_type {
+            FieldType::Text => (),
+            FieldType::List(_) => {
+                if !values.iter().any(|v| v == value) {
+                    return Err(lang::format("field_not_in_list", &[&self.label(), &values.join(", ")]));
+                }
+            },
+            FieldType::Number => {
+                if value.parse::<f64>().is_err() {
+                    return Err(lang::format("field_not_number", &[&self.label()]));
+                }
+            },
+            FieldType::Date => {
+                if NaiveDate::parse_from_str(value, DATE_FORMAT).is_err() {
+                    return Err(lang::format("field_not_date", &[&self.label()]));
+                }
+            },
+        }
+        if let Some(pattern) = &self.validation {
+            let re = Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| lang::format("field_invalid_validation", &[&self.name, &e]))?;
+            if !re.is_match(value) {
+                return Err(lang::format("field_not_matching", &[&self.label(), pattern]));
+            }
+        }
+        Ok(value.to_string())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let mut field = DefinitionField {
+            name: "Firmware".to_string(),
+            label: String::new(),
+            field_type: FieldType::Text,
+            required: true,
+            validation: Some(r"\d+\.\d+".to_string()),
+        };
+        assert_eq!(field.validate(" 1.20 ", &[]), Ok("1.20".to_string()));
+        assert!(field.validate("v1", &[]).is_err());
+        assert_eq!(field.validate("", &[]), Err("Firmware is required".to_string()));
+
+        field.validation = None;
+        field.required = false;
+        field.field_type = FieldType::Number;
+        assert_eq!(field.validate("", &[]), Ok(String::new()));
+        assert!(field.validate("12.5", &[]).is_ok());
+        assert!(field.validate("twelve", &[]).is_err());
+        field.field_type = FieldType::Date;
+        assert!(field.validate("01/02/2020", &[]).is_ok());
+        assert!(field.validate("2020-02-01", &[]).is_err());
+        field.field_type = FieldType::List(PathBuf::from("Customers.cfg"));
+        let customers = vec!("ACME".to_string());
+        assert!(field.validate("ACME", &customers).is_ok());
+        assert!(field.validate("Other", &customers).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use rev_lines::RevLines;
+use serde::{Serialize, Deserialize};
+
+use crate::Config;
+
+/// Source of interlock state of test stand
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum InterlockSource {
+    /// No source, interlock is always enabled
+    #[default]
+    None,
+    /// Interlock is enabled while file exists in flag folder of test stand
+    FlagFile {
+        file: PathBuf,
+    },
+    /// Last line of TC log containing one of the patterns
+    TcLog {
+        enabled: String,
+        disabled: String,
+    },
+    /// Last line starting with key in PLC export file, e.g. `Interlock=1`
+    PlcExport {
+        path: PathBuf,
+        key: String,
+        enabled_value: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interlock {
+    Enabled,
+    Disabled,
+}
+
+impl Interlock {
+    pub fn parse(text: &str) -> Option<Interlock> {
+        match text.trim() {
+            "Enabled" => Some(Interlock::Enabled),
+            "Disabled" => Some(Interlock::Disabled),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Interlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Interlock::Enabled => write!(f, "Enabled"),
+            Interlock::Disabled => write!(f, "Disabled"),
+        }
+    }
+}
+
+/// Newest file in folder
+fn newest_file(folder: &Path) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let mut out: Option<(PathBuf, std::time::SystemTime)> = None;
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+        let metadata = std::fs::metadata(&path)?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified()?;
+        if out.as_ref().is_none_or(|(_, time)| modified > *time) {
+            out = Some((path, modified));
+        }
+    }
+    Ok(out.map(|(path, _)| path))
+}
+
+/// State from last line of TC log with one of the patterns, enabled if there is no such line
+fn read_tc_log_state(path: PathBuf, enabled: &str, disabled: &str) -> Result<Interlock, Box<dyn Error>> {
+    let file = File::open(path)?;
+    for line in RevLines::new(io::BufReader::new(file))? {
+        if line.contains(disabled) {
+            return Ok(Interlock::Disabled);
+        } else if line.contains(enabled) {
+            return Ok(Interlock::Enabled);
+        }
+    }
+    Ok(Interlock::Enabled)
+}
+
+/// State from last line of PLC export starting with key, value can be separated by `=`, `:`, `;` or `,`.
+/// Key followed by other text, e.g. `InterlockReset` for key `Interlock`, is other key.
+pub fn parse_plc_export(contents: &str, key: &str, enabled_value: &str) -> Option<Interlock> {
+    let is_separator = |c: char| c.is_whitespace() || "=:;,".contains(c);
+    let line = contents.lines().rev().find(|l| {
+        l.trim().strip_prefix(key).and_then(|rest| rest.chars().next()).is_some_and(is_separator)
+    })?;
+    let value = line.trim()[key.len()..].trim_start_matches(is_separator);
+    if value.trim() == enabled_value {
+        Some(Interlock::Enabled)
+    } else {
+        Some(Interlock::Disabled)
+    }
+}
+
+/// Read actual interlock state from configured source
+pub fn read_interlock(config: &Config) -> Result<Interlock, Box<dyn Error>> {
+    match &config.interlock_source {
+        InterlockSource::None => Ok(Interlock::Enabled),
+        InterlockSource::FlagFile { file } => {
+            if config.get_flag_dir_path().join(file).exists() {
+                Ok(Interlock::Enabled)
+            } else {
+                Ok(Interlock::Disabled)
+            }
+        },
+        InterlockSource::TcLog { enabled, disabled } => {
+            match newest_file(&config.get_tc_log_folder_path())? {
+                Some(path) => read_tc_log_state(path, enabled, disabled),
+                None => Ok(Interlock::Enabled),
+            }
+        },
+        InterlockSource::PlcExport { path, key, enabled_value } => {
+            let contents = std::fs::read_to_string(path)?;
+            match parse_plc_export(&contents, key, enabled_value) {
+                Some(state) => Ok(state),
+                None => Err(From::from(format!("No line with key {} in PLC export: {:?}", key, path))),
+            }
+        },
+    }
+}
+
+/// Path to watch for interlock changes, TC log folder is watched also for state of measurement
+pub fn watch_path(config: &Config) -> Option<PathBuf> {
+    match &config.interlock_source {
+        InterlockSource::FlagFile { .. } => Some(config.get_flag_dir_path()),
+        InterlockSource::PlcExport { path, .. } => Some(path.to_owned()),
+        InterlockSource::TcLog { .. } => Some(config.get_tc_log_folder_path()),
+        InterlockSource::None => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plc_export() {
+        let export = "Pressure=12\r\nInterlock = 1\r\nInterlock;0\r\n";
+        assert_eq!(parse_plc_export(export, "Interlock", "1"), Some(Interlock::Disabled));
+        assert_eq!(parse_plc_export("Interlock: 1\r\n", "Interlock", "1"), Some(Interlock::Enabled));
+        assert_eq!(parse_plc_export(export, "Door", "1"), None);
+        let reset = "Interlock=1\r\nInterlockReset=0\r\n";
+        assert_eq!(parse_plc_export(reset, "Interlock", "1"), Some(Interlock::Enabled));
+    }
+}
@@ -0,0 +1,312 @@
+//! Catalog of operator facing messages in language of stand or operator
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::{Config, parse_config, read_text_file};
+
+/// Language of messages in code, used for missing translations
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// English messages, `{}` is replaced by arguments in order
+const ENGLISH: &[(&str, &str)] = &[
+    ("answer_yes", "yes, y"),
+    ("answer_no", "no, n"),
+    ("answer_end", "end, e"),
+    ("answer_pause", "pause, p, continue, con, c"),
+    ("running_on_stand", "Running measurement on test stand nm: {}"),
+    ("menu_welcome", "Welcome in MADL choose from options below:"),
+    ("menu_define", "d - Define test."),
+    ("menu_change", "c - Change time loss classification."),
+    ("menu_start", "s - Start test duration measurment"),
+    ("menu_pause", "p - Pause running test"),
+    ("menu_resume", "r - Resume paused test"),
+    ("menu_exit", "e - Exit"),
+    ("wrong_input", "Inserted wrong value, please insert again!"),
+    ("wrong_value", "Inserted wrong value: {}, please insert again!"),
+    ("wrong_number", "Inserted wrong number: {}, please insert again!"),
+    ("insert_again", "{}, please insert again!"),
+    ("pending_expired", "!!Pending definition from {} is older than {} hours, check values!!"),
+    ("pending_from", "Pending definition from {}:"),
+    ("unknown_time", "unknown time"),
+    ("definition_older", "Definition is older than {} hours!"),
+    ("no_pending", "No pending definition."),
+    ("pending_removed", "Pending definition removed."),
+    ("use_previous_values", "Use previous values?:"),
+    ("check_values", "Check values:"),
+    ("confirm_data", "Confirm data Yes/No >>"),
+    ("label_tr_number", "TR number"),
+    ("label_specimen_id", "Specimen ID"),
+    ("label_request_type", "Request type"),
+    ("label_test_category", "Test category"),
+    ("label_operator", "Operator"),
+    ("write_tr_number", "Write TR number:"),
+    ("tr_entry", "TR {}: {}, {}"),
+    ("tr_closed", "!!Warning: TR {} is closed!!"),
+    ("tr_not_in_registry", "!!Warning: TR {} is not in TR registry!!"),
+    ("tr_did_you_mean", "TR {} not in registry, did you mean:"),
+    ("tr_keep", "none, keep {}"),
+    ("write_specimen_id", "Write Specimen ID (more specimens separated by ';', position after '@', e.g. S1@A;S2@B):"),
+    ("specimen_not_expected", "!!Warning: Specimen {} is not expected for TR {}, expected: {}!!"),
+    ("registry_values", "Request type: {}, test category: {} and planned duration {} from TR registry"),
+    ("choose_request_type", "Choose request type:"),
+    ("choose_test_category", "Choose test category:"),
+    ("choose_operator", "Choose operator:"),
+    ("choose_field", "Choose {}:"),
+    ("write_field", "Write {}:"),
+    ("choose_classification", "Choose classification:"),
+    ("choose_sub_classification", "Choose sub-classification:"),
+    ("choose_time_loss", "Choose time loss clasification:"),
+    ("label_time_loss_classification", "Time loss classification"),
+    ("label_time_loss_sub_classification", "Time loss sub classification"),
+    ("label_time_loss_sub_category", "Time loss sub category"),
+    ("choose_test_end_reason", "Choose test end reason:"),
+    ("choose_sub_reason", "Choose sub-reason:"),
+    ("write_comment", "Write comment (empty for none):"),
+    ("classify_pause", "Classify reason of pause? (Yes/No):"),
+    ("end_or_pause", "End of test or pause? (End/Pause):"),
+    ("pause_reason_change", "Test is paused, change reason of pause:"),
+    ("test_paused", "Test paused!"),
+    ("test_resumed", "Test resumed!"),
+    ("no_test_running", "No test is running!"),
+    ("test_not_paused", "Test is not paused!"),
+    ("test_already_paused", "Test is already paused"),
+    ("paused_until_start", "Test paused, it is resumed at next test start"),
+    ("test_defined", "Test {} defined."),
+    ("time_loss_changed", "Time loss classification changed."),
+    ("pause_reason_changed", "Reason of pause changed."),
+    ("end_not_recorded", "End of test not recorded!"),
+    ("no_previous_record", "!!No record from previous measurement. Start testing again!!"),
+    ("interlock_changed", "Interlock {}!"),
+    ("interlock_disabled", "!!Interlock is disabled, test start is blocked!!"),
+    ("write_override_reason", "Write override reason to start measurement anyway (empty to not start):"),
+    ("interlock_blocks_start", "Measurement not started, interlock is disabled!"),
+    ("measurement_started", "Measurment started!"),
+    ("measurement_resumed", "Measurment resumed!"),
+    ("measurement_end", "Measurment end!"),
+    ("last_log_from_start", "!!Last log data are from start of test!!"),
+    ("already_stopped", "Last activity is already stoped: {}->{}->{}"),
+    ("empty_tc_line", "Empty line in TC log"),
+    ("specimen_removed", "Specimen {} removed from test."),
+    ("all_specimens_removed", "All specimens are removed, end the test!"),
+    ("write_split_time", "Write split time HH:MM:SS (empty to finish):"),
+    ("wrong_time", "Inserted wrong time: {}, please insert again!"),
+    ("split_time_range", "Split time must be after {} and before {}!"),
+    ("no_time_loss_interval", "No time loss interval in log: {}"),
+    ("choose_split_interval", "Choose time loss interval to split:"),
+    ("no_split_time", "No split time inserted, interval not changed."),
+    ("split_classification", "Classification for {} -> {}:"),
+    ("interval_split", "Interval split into {} parts."),
+    ("field_required", "{} is required"),
+    ("field_not_in_list", "{} has to be one of: {}"),
+    ("field_not_number", "{} has to be number"),
+    ("field_not_date", "{} has to be date dd/mm/yyyy"),
+    ("field_invalid_validation", "Invalid validation of field {}: {}"),
+    ("field_not_matching", "{} does not match {}"),
+    ("overrun_approaching", "Test of {} reaches planned duration {} in {}"),
+    ("overrun_exceeded", "Test of {} exceeded planned duration {} by {}"),
+];
+
+/// Translations built in binary, catalog files of settings folder can override them
+const BUNDLED: &[(&str, &str)] = &[
+    ("cs", include_str!("../lang/cs.yaml")),
+    ("pl", include_str!("../lang/pl.yaml")),
+    ("hi", include_str!("../lang/hi.yaml")),
+];
+
+/// Answer of operator to question
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Answer {
+    Yes,
+    No,
+    End,
+    /// Pause of running test, also continue later
+    Pause,
+}
+
+impl Answer {
+    fn key(&self) -> &'static str {
+        match self {
+            Answer::Yes => "answer_yes",
+            Answer::No => "answer_no",
+            Answer::End => "answer_end",
+            Answer::Pause => "answer_pause",
+        }
+    }
+
+    /// First of accepted answers in selected language
+    pub fn label(&self) -> String {
+        text(self.key()).split(',').next().unwrap_or_default().trim().to_string()
+    }
+}
+
+/// Messages of one language
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    pub language: String,
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Catalog of bundled translation and `<language>.yaml` from `dir`, file overrides bundled messages
+    pub fn load(language: &str, dir: &Path) -> Result<Catalog, Box<dyn Error>> {
+        let mut catalog = Catalog { language: language.to_string(), ..Default::default() };
+        if let Some((_, text)) = BUNDLED.iter().find(|(code, _)| *code == language) {
+            catalog.messages.extend(serde_yaml::from_str::<HashMap<String, String>>(text)?);
+        }
+        let path = dir.join(format!("{}.yaml", language));
+        if path.exists() {
+            catalog.messages.extend(serde_yaml::from_str::<HashMap<String, String>>(&read_text_file(&path)?)?);
+        } else if language != DEFAULT_LANGUAGE && !BUNDLED.iter().any(|(code, _)| *code == language) {
+            return Err(From::from(format!("No message catalog for language {}: {}", language, path.display())));
+        }
+        Ok(catalog)
+    }
+
+    /// Message in language of catalog or English if it is not translated
+    pub fn text(&self, key: &str) -> String {
+        match self.messages.get(key) {
+            Some(text) => text.to_owned(),
+            None => ENGLISH.iter().find(|(k, _)| *k == key).map_or(key, |(_, text)| text).to_string(),
+        }
+    }
+
+    /// Message with `{}` replaced by `args`
+    pub fn format(&self, key: &str, args: &[&dyn fmt::Display]) -> String {
+        let text = self.text(key);
+        let mut parts = text.split("{}");
+        let mut out = parts.next().unwrap_or_default().to_string();
+        for (i, part) in parts.enumerate() {
+            if let Some(arg) = args.get(i) {
+                out.push_str(&arg.to_string());
+            }
+            out.push_str(part);
+        }
+        out
+    }
+
+    /// Answer from `expected` matching inserted text, English answers are accepted in every language
+    pub fn answer(&self, input: &str, expected: &[Answer]) -> Option<Answer> {
+        let input = input.trim().to_lowercase();
+        expected.iter().copied().find(|answer| {
+            let english = ENGLISH.iter().find(|(k, _)| *k == answer.key()).map_or("", |(_, text)| text);
+            [english.to_string(), self.text(answer.key())].iter()
+                .flat_map(|list| list.split(',').map(|a| a.trim().to_lowercase()).collect::<Vec<_>>())
+                .any(|a| !a.is_empty() && a == input)
+        })
+    }
+}
+
+/// Languages of stand and operators from language cfg of stand,
+/// first line is language of stand, next lines are `operator, language`
+pub(crate) struct LanguageCfg {
+    pub stand: String,
+    pub operators: Vec<(String, String)>,
+}
+
+impl LanguageCfg {
+    pub fn new(path: &PathBuf) -> Result<LanguageCfg, Box<dyn Error>> {
+        let data = parse_config(&read_text_file(path)?);
+        let mut lines = data.iter().filter(|l| !l[0].trim().is_empty());
+        let stand = lines.next().map_or(DEFAULT_LANGUAGE, |l| l[0].trim()).to_string();
+        let operators = lines
+            .filter(|l| l.len() > 1)
+            .map(|l| (l[0].trim().to_string(), l[1].trim().to_string()))
+            .collect();
+        Ok(LanguageCfg { stand, operators })
+    }
+
+    pub fn create_empty(path: PathBuf) -> Result<(), Box<dyn Error>> {
+        if !path.exists() {
+            fs::write(path, DEFAULT_LANGUAGE)?;
+        }
+        Ok(())
+    }
+
+    /// Language of operator profile or of stand
+    pub fn language(&self, operator: Option<&str>) -> &str {
+        self.operators.iter()
+            .find(|(name, _)| Some(name.as_str()) == operator)
+            .map_or(self.stand.as_str(), |(_, language)| language.as_str())
+    }
+}
+
+static CATALOG: RwLock<Option<Catalog>> = RwLock::new(None);
+
+/// Select catalog of `operator` language or of stand language, English if catalog can't be read
+pub fn select(config: &Config, operator: Option<&str>) {
+    let path = config.get_config_file_path(&config.language_cfg);
+    let language = match LanguageCfg::new(&path) {
+        Ok(cfg) => cfg.language(operator.filter(|o| !o.is_empty())).to_string(),
+        Err(_) => DEFAULT_LANGUAGE.to_string(),
+    };
+    if CATALOG.read().unwrap().as_ref().is_some_and(|c| c.language == language) {
+        return;
+    }
+    let catalog = Catalog::load(&language, &config.settings_dir.join(&config.language_dir)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        Catalog { language: DEFAULT_LANGUAGE.to_string(), ..Default::default() }
+    });
+    *CATALOG.write().unwrap() = Some(catalog);
+}
+
+fn with_catalog<T>(f: impl FnOnce(&Catalog) -> T) -> T {
+    match CATALOG.read().unwrap().as_ref() {
+        Some(catalog) => f(catalog),
+        None => f(&Catalog::default()),
+    }
+}
+
+/// Message of selected catalog
+pub fn text(key: &str) -> String {
+    with_catalog(|c| c.text(key))
+}
+
+/// Message of selected catalog with `{}` replaced by `args`
+pub fn format(key: &str, args: &[&dyn fmt::Display]) -> String {
+    with_catalog(|c| c.format(key, args))
+}
+
+/// Answer of selected catalog matching inserted text
+pub fn answer(input: &str, expected: &[Answer]) -> Option<Answer> {
+    with_catalog(|c| c.answer(input, expected))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        let english = Catalog::default();
+        assert_eq!(english.format("tr_closed", &[&"TR1"]), "!!Warning: TR TR1 is closed!!");
+        assert_eq!(english.answer(" Y\n", &[Answer::Yes, Answer::No]), Some(Answer::Yes));
+        assert_eq!(english.answer("end", &[Answer::Yes, Answer::No]), None);
+
+        let dir = std::env::temp_dir();
+        let czech = Catalog::load("cs", &dir).unwrap();
+        assert_eq!(czech.text("choose_operator"), "Vyberte operátora:");
+        assert_eq!(czech.answer("Ano", &[Answer::Yes, Answer::No]), Some(Answer::Yes));
+        assert_eq!(czech.answer("no", &[Answer::Yes, Answer::No]), Some(Answer::No));
+        // Missing translation falls back to English
+        let partial = Catalog { language: "cs".to_string(), messages: HashMap::new() };
+        assert_eq!(partial.text("choose_operator"), "Choose operator:");
+        assert!(Catalog::load("xx", &dir).is_err());
+        for (code, _) in BUNDLED.iter() {
+            let catalog = Catalog::load(code, &dir).unwrap();
+            for key in catalog.messages.keys() {
+                assert!(ENGLISH.iter().any(|(k, _)| k == key), "Unknown key {} in {}", key, code);
+            }
+        }
+
+        let path = dir.join(format!("madl_test_language_{}.cfg", std::process::id()));
+        fs::write(&path, "pl\r\nJan Novak, cs\r\n").unwrap();
+        let cfg = LanguageCfg::new(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((cfg.language(Some("Jan Novak")), cfg.language(Some("Anna")), cfg.language(None)), ("cs", "pl", "pl"));
+    }
+}
@@ -11,13 +11,37 @@ use std::io;
 use std::sync::mpsc;
 use structopt::StructOpt;
 use std::path::PathBuf;
-use chrono::{DateTime, Local};
-use std::collections::{HashSet, HashMap, BTreeMap};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use std::collections::{HashSet, BTreeMap};
 use std::io::ErrorKind;
 use hotwatch::{Hotwatch, Event};
 use rev_lines::RevLines;
 use serde::{Serialize, Deserialize};
 
+pub mod report;
+pub mod record;
+pub mod interlock;
+pub mod registry;
+pub mod mqtt;
+pub mod storage;
+pub mod share;
+pub mod fields;
+pub mod lang;
+pub mod logfile;
+pub mod workflow;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "web")]
+pub mod web;
+#[cfg(feature = "api")]
+pub mod api;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+use interlock::{Interlock, InterlockSource};
+
+/// Format of timestamp in log records
+pub const LOG_TIME_FORMAT: &str = "%d/%m/%Y %H:%M:%S";
 
 #[derive(StructOpt)]
 #[structopt(name = "Madl",
@@ -29,73 +53,380 @@ pub struct Cli {
         //long = "cell",
         help="Test cell number")]
     pub cell: u8,
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+}
+
+#[derive(StructOpt)]
+pub enum Command {
+    /// Print intervals and time summary from log file
+    Report {
+        /// Day of log file in format YYYY-MM-DD, today if not set
+        #[structopt(long)]
+        date: Option<String>,
+    },
+    /// Split logged time loss interval into several classifications
+    Split {
+        /// Day of log file in format YYYY-MM-DD, today if not set
+        #[structopt(long)]
+        date: Option<String>,
+    },
+    /// Convert log files of older MADL version to current version, originals are kept
+    MigrateLogs,
+    /// Check hash chain of log records, reports first broken link
+    Verify,
+    /// Move old log files to monthly zip archives in log folder
+    ArchiveLogs {
+        /// Age of archived log files in days, `archive_after_days` of madl.cfg if not set
+        #[structopt(long)]
+        days: Option<i64>,
+    },
+    /// Show test definition waiting for start of measurement
+    Pending {
+        /// Remove pending definition
+        #[structopt(long)]
+        clear: bool,
+    },
+    /// Remove failed specimen from running test
+    RemoveSpecimen {
+        specimen: String,
+        /// Reason of removal
+        reason: String,
+    },
+    /// Import archived and current text log files to SQLite database configured as storage
+    #[cfg(feature = "sqlite")]
+    ImportLogs,
+    /// Full-screen terminal UI for operators
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Web dashboard and JSON API with state of all stands
+    #[cfg(feature = "web")]
+    Serve {
+        /// Address of server, e.g. 0.0.0.0 for access from network
+        #[structopt(long, default_value = "127.0.0.1")]
+        bind: String,
+        #[structopt(long, default_value = "8080")]
+        port: u16,
+        /// Token required in `Authorization: Bearer` header or `token` query parameter
+        #[structopt(long)]
+        token: Option<String>,
+    },
+}
+
+/// Parse date argument of command, today if not set
+pub fn parse_date_arg(date: &Option<String>) -> Result<DateTime<Local>, Box<dyn Error>> {
+    match date {
+        Some(text) => {
+            let day = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")?;
+            match Local.from_local_datetime(&day.and_hms_opt(12, 0, 0).unwrap()).earliest() {
+                Some(date) => Ok(date),
+                None => Err(From::from(format!("Invalid date: {}", text))),
+            }
+        },
+        None => Ok(Local::now()),
+    }
+}
+
+/// Origin of log record, test bench, stand number and host name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub bench: String,
+    pub stand: u8,
+    pub host: String,
+}
+
+impl Origin {
+    pub fn new(config: &Config) -> Result<Origin, Box<dyn Error>> {
+        let host = env::var("COMPUTERNAME")
+            .or_else(|_| env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        Ok(Origin { bench: config.get_test_bench_id()?, stand: config.stand_nm, host })
+    }
+
+    /// Parse origin from `ORIGIN=bench;stand;host` record field, parts are escaped by `record::escape_origin`
+    pub fn parse(field: &str) -> Option<Origin> {
+        let value = field.trim().strip_prefix("ORIGIN=")?;
+        let parts = record::split_escaped(value, ";");
+        if parts.len() < 3 {
+            return None;
+        }
+        Some(Origin {
+            bench: record::unescape_text(parts[0]),
+            stand: parts[1].parse().ok()?,
+            // Host with `;` written before escaping of origin
+            host: record::unescape_text(&parts[2..].join(";")),
+        })
+    }
+
+    /// Test bench id from log file name, `{bench}` of log file template
+    pub fn bench_from_path(config: &Config, path: &path::Path) -> Option<String> {
+        logfile::parse_name(config, path)?.bench
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ORIGIN={};{};{}", record::escape_origin(&self.bench), self.stand, record::escape_origin(&self.host))
+    }
 }
 
 ///State of measurement from last line in log
 pub enum Laststate {
     IN(Vec<String>),
     OUT(Vec<String>),
+    /// Running test is paused, fields of PAUSE record
+    PAUSE(Vec<String>),
     EMPTY,
 }
 
-/// Temporary definition file created with flag -d
+/// Test definition written to log before start of test.
+/// Aliases are keys of log file and of pending definition version 1.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestDefinition {
+    #[serde(alias = "InterlockStatus")]
+    pub interlock_status: String,
+    #[serde(alias = "TR_Number")]
+    pub tr_number: String,
+    /// Specimens of test run separated by `;`, see `Specimen`
+    #[serde(alias = "Specimen ID")]
+    pub specimen_id: String,
+    #[serde(alias = "Test Request type")]
+    pub request_type: String,
+    #[serde(alias = "Testing_Category")]
+    pub testing_category: String,
+    #[serde(alias = "Technician")]
+    pub technician: String,
+    #[serde(alias = "Available Time")]
+    pub available_time: String,
+    /// Values of custom fields configured in `definition_fields`
+    pub fields: BTreeMap<String, String>,
+}
+
+impl TestDefinition {
+    /// Keys of definition lines in log file
+    pub const KEYS: [&'static str; 7] = ["InterlockStatus", "TR_Number", "Specimen ID", "Test Request type",
+        "Testing_Category", "Technician", "Available Time"];
+
+    /// Values with keys of log file
+    pub fn values(&self) -> [(&'static str, &str); 7] {
+        [
+            ("InterlockStatus", self.interlock_status.as_str()),
+            ("TR_Number", self.tr_number.as_str()),
+            ("Specimen ID", self.specimen_id.as_str()),
+            ("Test Request type", self.request_type.as_str()),
+            ("Testing_Category", self.testing_category.as_str()),
+            ("Technician", self.technician.as_str()),
+            ("Available Time", self.available_time.as_str()),
+        ]
+    }
+
+    pub fn specimens(&self) -> Vec<Specimen> {
+        Specimen::parse_list(&self.specimen_id)
+    }
+
+    /// Set value of log file key, returns false for key not in definition
+    pub fn set(&mut self, key: &str, value: String) -> bool {
+        let field = match key {
+            "InterlockStatus" => &mut self.interlock_status,
+            "TR_Number" => &mut self.tr_number,
+            "Specimen ID" => &mut self.specimen_id,
+            "Test Request type" => &mut self.request_type,
+            "Testing_Category" => &mut self.testing_category,
+            "Technician" => &mut self.technician,
+            "Available Time" => &mut self.available_time,
+            _ => return false,
+        };
+        *field = value;
+        true
+    }
+}
+
+/// Specimen of test run, written in `Specimen ID` as `id` or `id@position`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Specimen {
+    pub id: String,
+    /// Position or slot on rig
+    pub position: Option<String>,
+}
+
+impl Specimen {
+    /// Specimens from `;` separated list
+    pub fn parse_list(text: &str) -> Vec<Specimen> {
+        text.split(';')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.split_once('@') {
+                Some((id, position)) => Specimen { id: id.trim().to_string(), position: Some(position.trim().to_string()) },
+                None => Specimen { id: s.to_string(), position: None },
+            })
+            .collect()
+    }
+
+    pub fn format_list(specimens: &[Specimen]) -> String {
+        specimens.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(";")
+    }
+}
+
+impl fmt::Display for Specimen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.position {
+            Some(position) => write!(f, "{}@{}", self.id, position),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Last test definition and last IN/OUT record line from log
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LastLogState {
+    pub definition: TestDefinition,
+    pub last_line: String,
+}
+
+/// Version of pending definition file written by this build
+pub const PENDING_VERSION: u32 = 2;
+
+/// Test definition waiting for start of measurement.
+/// Version 1 was map of definition keys without version and time.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PendingDefinition {
+    pub version: u32,
+    /// Time of definition, empty for version 1
+    pub created: String,
+    #[serde(flatten)]
+    pub definition: TestDefinition,
+}
+
+impl PendingDefinition {
+    pub fn new(definition: &TestDefinition, created: NaiveDateTime) -> PendingDefinition {
+        PendingDefinition {
+            version: PENDING_VERSION,
+            created: created.format(LOG_TIME_FORMAT).to_string(),
+            definition: definition.clone(),
+        }
+    }
+
+    /// Age of definition, `None` if time is unknown
+    pub fn age(&self, now: NaiveDateTime) -> Option<chrono::Duration> {
+        NaiveDateTime::parse_from_str(&self.created, LOG_TIME_FORMAT).ok().map(|created| now - created)
+    }
+}
+
+/// Pending definition file in folder of test stand, created with flag -d
 /// Data from file written to lag after start of measurement, flag -s
 pub struct DefFile {
     path: path::PathBuf,
+    /// File of older versions in temp folder of OS
+    legacy_path: path::PathBuf,
+    expiry_hours: i64,
 }
 
 impl DefFile {
     pub fn new(config: &Config) -> DefFile {
-        let mut dir = env::temp_dir();
-        dir.push(&config.teststand_dir);
-        let mut dir: path::PathBuf = match fs::create_dir(&dir) {
-            Ok(_) => dir,
-            Err(ref error) if error.kind() == ErrorKind::AlreadyExists => dir,
-            Err(error) => {
-                panic!(
-                    "There was a problem opening the file: {:?}",
-                    error
-                )
-            },
-        };
-        dir.push(&config.temp_file);
-        DefFile{path: dir}
+        DefFile {
+            path: config.settings_dir.join(&config.teststand_dir).join(&config.temp_file),
+            legacy_path: env::temp_dir().join(&config.teststand_dir).join(&config.temp_file),
+            expiry_hours: config.pending_expiry_hours,
+        }
+    }
+
+    /// Check if there is definition waiting for start of measurement
+    pub fn exists(&self) -> bool {
+        self.path.exists() || self.legacy_path.exists()
     }
 
     /// Remove tempfile after data written to log file.
     pub fn remove_temp_file(&self) -> Result<(), Box<dyn Error>> {
-        if self.path.exists() {
-            fs::remove_file(&self.path)?;
-            Ok(())
-        } else {
-            //eprintln!("Temp file not exist! {:?}", self.path);
-            Ok(())
+        for path in [&self.path, &self.legacy_path].iter() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
         }
+        Ok(())
     }
 
-    ///Write output data to temp file
-    pub fn write_temp_output(&self, output: &HashMap<&str, String>) -> Result<(), Box<dyn Error>> {
-        let f = fs::OpenOptions::new().write(true).create(true).open(&self.path)?;
-        serde_yaml::to_writer(f, output)?;
+    ///Write definition to temp file. Data are written to new file renamed over old one,
+    ///interrupted write keeps previous definition.
+    pub fn write_temp_output(&self, definition: &TestDefinition) -> Result<(), Box<dyn Error>> {
+        let definition = PendingDefinition::new(definition, Local::now().naive_local());
+        let new_path = self.path.with_extension("new");
+        let f = fs::OpenOptions::new().write(true).create(true).truncate(true).open(&new_path)?;
+        serde_yaml::to_writer(&f, &definition)?;
+        f.sync_all()?;
+        fs::rename(&new_path, &self.path)?;
+        if self.legacy_path.exists() {
+            fs::remove_file(&self.legacy_path)?;
+        }
         Ok(())
     }
 
-    // if temp file exist modify output from him if not return w/o change
-    pub fn read_temp_output<'a>(&self, mut output: HashMap<&'a str, String>) -> std::io::Result<HashMap<&'a str, String>> {
-        //println!("{:?}", self.path);
-        if self.path.exists() {
-            let file = File::open(&self.path)?;
-            let reader = io::BufReader::new(file);
-            let value: HashMap<String, String>  = match serde_yaml::from_reader(reader) {
-                Ok(val) => val,
-                Err(_) => return Ok(output),
-            };
-            for (i, val) in output.iter_mut() {
-                *val = value.get::<str>(&i).unwrap().to_string();
-            }
+    /// Pending definition, file of older version is read from temp folder of OS
+    pub fn read(&self) -> Result<Option<PendingDefinition>, Box<dyn Error>> {
+        let path = match (self.path.exists(), self.legacy_path.exists()) {
+            (true, _) => &self.path,
+            (false, true) => &self.legacy_path,
+            (false, false) => return Ok(None),
+        };
+        let file = File::open(path)?;
+        let definition: PendingDefinition = match serde_yaml::from_reader(io::BufReader::new(file)) {
+            Ok(val) => val,
+            Err(e) => return Err(From::from(format!("Pending definition {} not readable: {}", path.display(), e))),
+        };
+        if definition.version > PENDING_VERSION {
+            eprintln!("Pending definition is from newer MADL version {}, unknown values are ignored.", definition.version);
         }
-        Ok(output)
+        Ok(Some(definition))
+    }
+
+    /// Check if definition is older than configured expiry
+    pub fn is_expired(&self, definition: &PendingDefinition) -> bool {
+        definition.age(Local::now().naive_local()).is_some_and(|age| age.num_hours() >= self.expiry_hours)
+    }
+
+    // if temp file exist return definition from him if not return w/o change
+    pub fn read_temp_output(&self, definition: TestDefinition) -> Result<TestDefinition, Box<dyn Error>> {
+        let pending = match self.read() {
+            Ok(Some(pending)) => pending,
+            Ok(None) => return Ok(definition),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Ok(definition);
+            },
+        };
+        if self.is_expired(&pending) {
+            println!("\n{}", lang::format("pending_expired", &[&pending.created, &self.expiry_hours]));
+        }
+        Ok(pending.definition)
+    }
+}
+
+/// Show pending definition of stand, remove it with `clear`
+pub fn show_pending(config: &Config, clear: bool) -> Result<(), Box<dyn Error>> {
+    let deffile = DefFile::new(config);
+    match deffile.read() {
+        Ok(Some(pending)) => {
+            let created = if pending.created.is_empty() { lang::text("unknown_time") } else { pending.created.to_owned() };
+            println!("{}", lang::format("pending_from", &[&created]));
+            for (key, value) in pending.definition.values().iter() {
+                println!("  {}: {}", key, value);
+            }
+            for (name, value) in pending.definition.fields.iter() {
+                println!("  {}: {}", name, value);
+            }
+            if deffile.is_expired(&pending) {
+                println!("{}", lang::format("definition_older", &[&config.pending_expiry_hours]));
+            }
+        },
+        Ok(None) => println!("{}", lang::text("no_pending")),
+        Err(e) => println!("{}", e),
+    }
+    if clear && deffile.exists() {
+        deffile.remove_temp_file()?;
+        println!("{}", lang::text("pending_removed"));
     }
+    Ok(())
 }
 
 /// Create config file structure if was not defined before
@@ -104,9 +435,7 @@ pub fn create_config_files(config: &Config) -> () {
             .join(&config.teststand_dir)
             .join(&config.config_dir);
     let log_dir = config.get_log_dir_path();
-    let flag_dir = config.settings_dir
-            .join(&config.teststand_dir)
-            .join(&config.flag_dir);
+    let flag_dir = config.get_flag_dir_path();
 
     for d in vec!(&dir, &log_dir, &flag_dir) {
         if !d.exists() {
@@ -126,27 +455,32 @@ pub fn create_config_files(config: &Config) -> () {
         TestInfo::create_empty(dir.join(&config.test_bench_id_cfg)).unwrap();
         TestCategory::create_empty(dir.join(&config.test_category_cfg)).unwrap();
         TestInfo::create_empty(dir.join(&config.test_request_type_cfg)).unwrap();
-        TestInfo::create_empty(dir.join(&config.test_stop_reason_list_cfg)).unwrap();
+        TestStopReasons::create_empty(dir.join(&config.test_stop_reason_list_cfg)).unwrap();
         TestLossClass::create_empty(dir.join(&config.timeloss_classification_cfg)).unwrap();
         TestInfo::create_empty(dir.join(&config.user_data_cfg)).unwrap();
         TestInfo::create_empty(dir.join(&config.user_preference_cfg)).unwrap();
+        registry::TrRegistry::create_empty(dir.join(&config.tr_registry_cfg)).unwrap();
+        lang::LanguageCfg::create_empty(dir.join(&config.language_cfg)).unwrap();
+        for field in config.definition_fields.iter() {
+            if let fields::FieldType::List(cfg) = &field.field_type {
+                TestInfo::create_empty(dir.join(cfg)).unwrap();
+            }
+        }
     };
 
     ()
 }
 
 /// Check state from last line in log file. (If measurement started or etc.)
-pub fn check_state(output: &HashMap<&str, String>) -> Laststate {
-    let last_line = match output.get(&"last_line") {
-        Some(text) => text,
-        None => panic!("Not defined last line in Output HashMap!"),
-    };
-    let state_vec: Vec<String> = last_line.trim().split("::").map(String::from).collect();
+pub fn check_state(state: &LastLogState) -> Laststate {
+    let (fields, _tags) = record::split_fields(&state.last_line);
+    let state_vec: Vec<String> = fields.into_iter().map(record::unescape_text).collect();
     //println!("Check_state: {:?}", state_vec);
     match state_vec[0].as_ref() {
-        "IN" => return Laststate::IN(state_vec[2..].to_vec()),
-        "OUT" => return Laststate::OUT(state_vec[2..].to_vec()),
-        _ => return Laststate::EMPTY,
+        "IN" => Laststate::IN(state_vec[2..].to_vec()),
+        "OUT" => Laststate::OUT(state_vec[2..].to_vec()),
+        "PAUSE" => Laststate::PAUSE(state_vec[2..].to_vec()),
+        _ => Laststate::EMPTY,
     }
 }
 
@@ -155,114 +489,208 @@ pub fn read_text_file(path: &PathBuf) -> Result<String, Box<dyn Error>> {
     //println!("Reading config: {:?}", path);
     let mut f = match File::open(path) {
         Ok(f) => f,
-        Err(e) => panic!("Cant open file path: {:?}\nError: {:?}", path, e),
+        Err(e) => return Err(From::from(format!("Cant open file path: {:?}\nError: {:?}", path, e))),
     };
     let mut contents = String::new();
     f.read_to_string(&mut contents)?;
     Ok(contents)
 }
 
-/// Parse string to Vec of Vec of String
+/// Parse string to Vec of Vec of String, values are separated by not escaped comma
 pub fn parse_config(raw_string: &str) -> Vec<Vec<String>> {
     raw_string.lines()
-        .map(|s| s.trim().split(',').map(String::from).collect::<Vec<_>>())
+        .map(|s| record::split_escaped(s.trim(), ",").into_iter().map(record::unescape_text).collect::<Vec<_>>())
         .collect::<Vec<_>>()
 }
 
-/// Read last test setting from log file
-pub fn get_log_data(fpath: path::PathBuf, mut output: HashMap<&str, String>) -> Result<HashMap<&str, String>, Box<dyn Error>> {
-    //println!("file path for output: {:?}", fpath);
-    let mut keys: HashSet<&str> = output.keys().map(|e| *e).collect();
-    if !fpath.exists() {
-        return Ok(output);
-    }
-    let contents = read_text_file(&fpath)?;
+/// Escape value of config line for `parse_config`, comma is written as `\,`
+pub fn escape_config(value: &str) -> String {
+    record::escape_chars(value, |chars, i| chars[i] == ',')
+}
+
+/// Read last test setting with values of custom `fields` from log file
+pub fn get_log_data(fpath: path::PathBuf, fields: &[fields::DefinitionField]) -> Result<LastLogState, Box<dyn Error>> {
+    get_logs_data(&[fpath], fields)
+}
 
-    for (l, line) in contents.lines().rev().enumerate() {
-        //println!("{}", &line);
-        if l == 0 {
-            let _val = output.insert(&"last_line", line.to_string());
+/// Read last test setting with values of custom `fields` from log files sorted by date.
+/// Earlier files are read until state record and test definition are found, test can continue from previous day.
+pub fn get_logs_data(paths: &[path::PathBuf], fields: &[fields::DefinitionField]) -> Result<LastLogState, Box<dyn Error>> {
+    let mut state = LastLogState::default();
+    let mut keys: HashSet<&str> = TestDefinition::KEYS.iter().copied().collect();
+    keys.extend(fields.iter().map(|f| f.name.as_str()));
+
+    let mut last_found = false;
+    let mut definition_found = false;
+    let mut resumed = false;
+    for fpath in paths.iter().rev() {
+        if !fpath.exists() {
+            continue;
         }
+        let contents = read_text_file(fpath)?;
+        for line in contents.lines().rev() {
+            //println!("{}", &line);
+            // Only IN, OUT and not resumed PAUSE records change state of measurement
+            if !last_found && line.starts_with("RESUME::") {
+                resumed = true;
+            } else if !last_found && ((line.starts_with("PAUSE::") && !resumed) || line.starts_with("IN::") || line.starts_with("OUT::")) {
+                state.last_line = line.to_string();
+                last_found = true;
+            }
 
-        let linevec: Vec<&str> = line.trim().split("::").collect();
+            let linevec: Vec<String> = record::split_escaped(line.trim(), "::").into_iter().map(record::unescape_text).collect();
 
-        match keys.take(linevec[0]) {
-            Some(key) => output.insert(key, linevec[1].to_string()),
-            None => continue,
-        };
+            match keys.take(linevec[0].as_str()) {
+                Some(key) => if !state.definition.set(key, linevec[1].to_owned()) {
+                    state.definition.fields.insert(key.to_string(), linevec[1].to_owned());
+                },
+                None => continue,
+            };
+            definition_found = true;
 
-        if keys.is_empty() {
+            if keys.is_empty() && last_found {
+                return Ok(state);
+            }
+        }
+        if last_found && definition_found {
             break
         }
     }
-    Ok(output)
+    Ok(state)
 }
 
-/// Get last modified log path
-fn last_modified_log(config: &Config, current_dir: &path::PathBuf) -> Result<path::PathBuf, Box<dyn Error>> {
-    //println!("Entries modified in the last 24 hours in {:?}:", current_dir);
-    let local: DateTime<Local> = Local::now();
-    let filename = config.get_log_file_path(local)?;
-    let mut out: path::PathBuf = filename;
-    let mut timediff: Option<u64> = None;
+/// Last test definition and state from log
+pub fn read_last_state(config: &Config) -> Result<LastLogState, Box<dyn Error>> {
+    match storage::open(config, Local::now())?.last() {
+        Some(storage) => storage.log_data(),
+        None => Ok(LastLogState::default()),
+    }
+}
+
+/// Select language of operator from pending definition or from last test
+pub fn select_language(config: &Config) {
+    let operator = match DefFile::new(config).read() {
+        Ok(Some(pending)) => Some(pending.definition.technician),
+        _ => read_last_state(config).ok().map(|state| state.definition.technician),
+    };
+    lang::select(config, operator.as_deref());
+}
 
-    for entry in fs::read_dir(current_dir)? {
-        let entry = entry?;
-        let path = entry.path();
+/// Level of warning about planned test duration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Overrun {
+    None,
+    Approaching,
+    Exceeded,
+}
 
-        let metadata = fs::metadata(&path)?;
-        let last_modified = metadata.modified()?.elapsed()?.as_secs();
+/// Test time of running TR from all logs against planned duration
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestProgress {
+    pub tr_number: String,
+    pub planned: Duration,
+    pub elapsed: Duration,
+    /// Time when elapsed time was counted
+    pub time: record::LogTime,
+}
 
-        match timediff {
-            Some(diff) => if {last_modified < diff} && metadata.is_file() {
-                out = path.to_path_buf();
-                timediff = Some(last_modified);
-            },
-            None => {
-                timediff = Some(last_modified);
-                out = path.to_path_buf();
-            },
+impl TestProgress {
+    /// Elapsed time of running test at `now`
+    pub fn elapsed_at(&self, now: record::LogTime) -> Duration {
+        self.elapsed + (now - self.time)
+    }
+
+    pub fn overrun(&self, now: record::LogTime, warning: Duration) -> Overrun {
+        let elapsed = self.elapsed_at(now);
+        if elapsed > self.planned {
+            Overrun::Exceeded
+        } else if elapsed + warning >= self.planned {
+            Overrun::Approaching
+        } else {
+            Overrun::None
         }
     }
 
-    Ok(out)
+    /// Text of warning for operator, `None` if test is within planned duration
+    pub fn warning(&self, now: record::LogTime, warning: Duration) -> Option<String> {
+        let elapsed = self.elapsed_at(now);
+        match self.overrun(now, warning) {
+            Overrun::None => None,
+            Overrun::Approaching => Some(lang::format("overrun_approaching",
+                &[&self.tr_number, &report::format_duration(self.planned), &report::format_duration(self.planned - elapsed)])),
+            Overrun::Exceeded => Some(lang::format("overrun_exceeded",
+                &[&self.tr_number, &report::format_duration(self.planned), &report::format_duration(elapsed - self.planned)])),
+        }
+    }
 }
 
-/// Initialize output with data from log file
-pub fn update_output<'a>(config: &Config, output: &HashMap<&'a str, String>) -> Result<HashMap<&'a str, String>, Box<dyn Error>> {
-    let dirpath = config.get_log_dir_path();
-    match last_modified_log(config, &dirpath) {
-        Ok(file_path) => {
-            let output = get_log_data(file_path, output.to_owned())?;
-            return Ok(output);
-        },
-        Err(e) => {
-            eprintln!("Empty dir or no file: {:?}\nError {:?}", &dirpath, e);
-            return Ok(output.to_owned());
-        },
+/// Progress of running test, `None` if no test is running or its duration is not planned
+pub fn test_progress(config: &Config) -> Result<Option<TestProgress>, Box<dyn Error>> {
+    let state = read_last_state(config)?;
+    match check_state(&state) {
+        Laststate::IN(vec_data) if vec_data[0].contains("Test Start") => (),
+        _ => return Ok(None),
     }
+    let planned = match report::planned_duration(&state.definition.available_time) {
+        Some(planned) => planned,
+        None => return Ok(None),
+    };
+    let now = record::now();
+    let runs = log_history(config)?.runs;
+    let elapsed = match report::tr_summary(&runs, now).remove(&state.definition.tr_number) {
+        Some(time) => time.actual,
+        None => return Ok(None),
+    };
+    Ok(Some(TestProgress { tr_number: state.definition.tr_number, planned, elapsed, time: now }))
 }
 
-/// Format test definition
-fn format_output(output: &HashMap<&str, String>) -> String {
+/// Warning when running test reaches higher overrun level than `warned`, alert is also published over MQTT
+pub fn overrun_warning(config: &Config, progress: &TestProgress, warned: &mut Overrun) -> Option<String> {
+    let now = record::now();
+    let warning = Duration::minutes(config.overrun_warning_minutes);
+    let level = progress.overrun(now, warning);
+    let raised = level > *warned;
+    *warned = level;
+    if !raised {
+        return None;
+    }
+    let text = progress.warning(now, warning)?;
+    #[cfg(feature = "mqtt")]
+    if let Err(e) = mqtt::publish_alert(&text) {
+        eprintln!("MQTT alert not published: {}", e);
+    }
+    Some(text)
+}
 
-    let text = format!("\
-MADL_Version::2.5\r\n\
-InterlockStatus::Enabled\r\n\
+/// Format test definition
+fn format_output(definition: &TestDefinition, fields: &[fields::DefinitionField], origin: &Origin, interlock: Interlock) -> String {
+    use record::escape_text as escape;
+
+    let mut text = format!("\
+MADL_Version::{}\r\n\
+TestBench ID::{}\r\n\
+Stand Number::{}\r\n\
+Host Name::{}\r\n\
+InterlockStatus::{}\r\n\
 TR_Number::{}\r\n\
 Specimen ID::{}\r\n\
 Test Request type::{}\r\n\
 Testing_Category::{}\r\n\
 Technician::{}\r\n\
 Available Time::{}\r\n",
-    output[&"TR_Number"], output[&"Specimen ID"], output[&"Test Request type"],
-    output[&"Testing_Category"], output[&"Technician"], output[&"Available Time"]);
+    record::MADL_VERSION, escape(&origin.bench), origin.stand, escape(&origin.host), interlock,
+    escape(&definition.tr_number), escape(&definition.specimen_id), escape(&definition.request_type),
+    escape(&definition.testing_category), escape(&definition.technician), escape(&definition.available_time));
+    for field in fields.iter() {
+        let value = definition.fields.get(&field.name).map_or("", |v| v.as_str());
+        text.push_str(&format!("{}::{}\r\n", escape(&field.name), escape(value)));
+    }
 
     text
 }
 
 /// One line config data
-struct TestInfo {
+pub(crate) struct TestInfo {
     pub values: Vec<String>,
 }
 
@@ -283,31 +711,6 @@ impl TestInfo {
         Ok(())
     }
 
-    pub fn choose_value(&self) -> Result<String, Box<dyn Error>> {
-        loop {
-            let mut str_input = String::new();
-            println!("{}", self);
-            print!(">>");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut str_input)
-                    .expect("Failed to read line!");
-            let lenght: usize = self.values.len();
-            match str_input.trim().parse::<usize>() {
-                Ok(num) => {
-                    if num <= lenght {
-                        return Ok(self.values[num].to_owned());
-                    } else {
-                        println!("Inserted wrong number: {}, please insert again!\n", str_input);
-                        continue;
-                    }
-                },
-                Err(e) => {
-                    println!("Inserted wrong value: {}, please insert again!\n{:?}\n", str_input, e);
-                    continue;
-                },
-            };
-        }
-    }
 }
 
 impl fmt::Display for TestInfo {
@@ -322,8 +725,8 @@ impl fmt::Display for TestInfo {
 }
 
 /// One line config file with associated number with '*' operator
-struct TestCategory {
-    values: BTreeMap<String, String>,
+pub(crate) struct TestCategory {
+    pub values: BTreeMap<String, String>,
 }
 
 impl TestCategory {
@@ -350,33 +753,6 @@ impl TestCategory {
         Ok(())
     }
 
-    pub fn choose_value(&self) -> Result<(&String, &String), Box<dyn Error>> {
-
-        loop {
-            let mut str_input = String::new();
-            println!("{}", self);
-            print!(">>");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut str_input)
-                    .expect("Failed to read input.");
-            let lenght: usize = self.values.len();
-            match str_input.trim().parse::<usize>() {
-                Ok(num) => {
-                    if num <= lenght {
-                        let (key, val) = self.values.iter().nth(num).unwrap();
-                        return Ok((key, val));
-                    } else {
-                        println!("Inserted wrong number: {}, please insert again!\n", str_input);
-                        continue;
-                    }
-                },
-                Err(e) => {
-                    println!("Inserted wrong value: {}, please insert again!\n{:?}\n", str_input, e);
-                    continue;
-                },
-            };
-        }
-    }
 }
 
 impl fmt::Display for TestCategory {
@@ -391,8 +767,8 @@ impl fmt::Display for TestCategory {
 }
 
 /// Multi line config file with dependent values
-struct TestLossClass {
-    values: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+pub(crate) struct TestLossClass {
+    pub values: BTreeMap<String, BTreeMap<String, Vec<String>>>,
 }
 
 impl TestLossClass {
@@ -443,12 +819,12 @@ Idle Time,No test sample,Sample Shortage\r\n")?;
                     if num <= lenght {
                         return Ok(values[num].to_owned());
                     } else {
-                        println!("Inserted wrong number: {}, please insert again!\n", str_input);
+                        println!("{}\n", lang::format("wrong_number", &[&str_input]));
                         continue;
                     }
                 },
                 Err(e) => {
-                    println!("Inserted wrong value: {}, please insert again!\n{:?}\n", str_input, e);
+                    println!("{}\n{:?}\n", lang::format("wrong_value", &[&str_input]), e);
                     continue;
                 },
             };
@@ -456,16 +832,16 @@ Idle Time,No test sample,Sample Shortage\r\n")?;
     }
 
     pub fn choose_value(&self) -> Result<Vec<String>, Box<dyn Error>> {
-        println!("\nChoose classification:");
+        println!("\n{}", lang::text("choose_classification"));
         let firstlevel: Vec<&String> = self.values.keys().collect();
         let first = self.read_input(&firstlevel)?;
 
-        println!("\nChoose sub-classification:");
+        println!("\n{}", lang::text("choose_sub_classification"));
         let secondlevelval = self.values.get(&first).unwrap();
         let secondlevel: Vec<&String> = secondlevelval.keys().collect();
         let second = self.read_input(&secondlevel)?;
 
-        println!("\nChoose sub-classification:");
+        println!("\n{}", lang::text("choose_sub_classification"));
         let thirdlevel = secondlevelval.get(&second).unwrap();
         let thirdlevel: Vec<&String> = thirdlevel.iter().collect();
         let third = self.read_input(&thirdlevel)?;
@@ -475,6 +851,124 @@ Idle Time,No test sample,Sample Shortage\r\n")?;
     }
 }
 
+/// Result of ended test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestResult {
+    Pass,
+    Fail,
+    Aborted,
+}
+
+impl TestResult {
+    pub fn parse(text: &str) -> Option<TestResult> {
+        match text.trim().to_lowercase().as_ref() {
+            "pass" => Some(TestResult::Pass),
+            "fail" => Some(TestResult::Fail),
+            "aborted" => Some(TestResult::Aborted),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestResult::Pass => write!(f, "pass"),
+            TestResult::Fail => write!(f, "fail"),
+            TestResult::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+/// Reason of test end with optional sub-reason and result
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopReason {
+    pub reason: String,
+    pub sub_reason: Option<String>,
+    pub result: Option<TestResult>,
+}
+
+impl StopReason {
+    /// Parse config entry `reason/sub reason*result`, sub-reason and result are optional
+    pub fn parse(entry: &str) -> Result<StopReason, Box<dyn Error>> {
+        let (path, result) = match entry.split_once('*') {
+            Some((path, result)) => match TestResult::parse(result) {
+                Some(result) => (path, Some(result)),
+                None => return Err(From::from(format!("Unknown test result in stop reason: {}", entry))),
+            },
+            None => (entry, None),
+        };
+        let (reason, sub_reason) = match path.split_once('/') {
+            Some((reason, sub)) => (reason.trim(), Some(sub.trim().to_string())),
+            None => (path.trim(), None),
+        };
+        Ok(StopReason { reason: reason.to_string(), sub_reason, result })
+    }
+
+    /// Fields of OUT record after `Test Stopped`
+    pub fn fields(&self) -> Vec<String> {
+        let mut out = vec!(self.reason.to_owned(), self.sub_reason.clone().unwrap_or_else(|| "none".to_string()));
+        if let Some(result) = self.result {
+            out.push(result.to_string());
+        }
+        out
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.reason)?;
+        if let Some(sub) = &self.sub_reason {
+            write!(f, " / {}", sub)?;
+        }
+        if let Some(result) = self.result {
+            write!(f, " ({})", result)?;
+        }
+        Ok(())
+    }
+}
+
+/// Config file of stop reasons, comma separated entries `reason/sub reason*result`
+pub(crate) struct TestStopReasons {
+    pub values: Vec<StopReason>,
+}
+
+impl TestStopReasons {
+    pub fn new(path: &path::PathBuf) -> Result<TestStopReasons, Box<dyn Error>> {
+        let config_str = read_text_file(path)?;
+        let mut values = Vec::new();
+        for entry in parse_config(&config_str).iter().flatten().filter(|e| !e.trim().is_empty()) {
+            values.push(StopReason::parse(entry)?);
+        }
+        Ok(TestStopReasons { values })
+    }
+
+    pub fn create_empty(path: PathBuf) -> Result<(), Box<dyn Error>> {
+        if !path.exists() {
+            let mut f = fs::OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+            f.write_all(b"Finished/Target reached*pass, Finished/Specimen failure*fail, Aborted/Stand failure*aborted")?;
+        };
+        Ok(())
+    }
+
+    /// Top level reasons in order of config
+    pub fn reasons(&self) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        for value in self.values.iter() {
+            if !out.contains(&value.reason) {
+                out.push(value.reason.to_owned());
+            }
+        }
+        out
+    }
+
+    /// Entries of reason, more entries are sub-reasons
+    pub fn entries(&self, reason: &str) -> Vec<&StopReason> {
+        self.values.iter().filter(|v| v.reason == reason).collect()
+    }
+
+}
+
 impl fmt::Display for TestLossClass {
     // This trait requires `fmt` with this exact signature.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -492,8 +986,11 @@ impl fmt::Display for TestLossClass {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Number of test stand from command line, not stored in config file
+    #[serde(skip)]
+    pub stand_nm: u8,
     pub settings_dir: path::PathBuf,
     pub teststand_dir: path::PathBuf,
     pub flag_dir: path::PathBuf,
@@ -510,6 +1007,38 @@ pub struct Config {
     pub temp_file: path::PathBuf,
     pub tc_root_folder: path::PathBuf,
     pub tc_log_folder: path::PathBuf,
+    #[serde(default)]
+    pub interlock_source: InterlockSource,
+    #[serde(default = "Config::default_tr_registry_cfg")]
+    pub tr_registry_cfg: path::PathBuf,
+    #[serde(default)]
+    pub mqtt: Option<mqtt::MqttConfig>,
+    #[serde(default)]
+    pub storage: storage::StorageBackend,
+    /// Secondary destination of text logs, e.g. mounted network share
+    #[serde(default)]
+    pub share_dir: Option<path::PathBuf>,
+    /// Age of pending definition in hours for warning at start of measurement
+    #[serde(default = "Config::default_pending_expiry_hours")]
+    pub pending_expiry_hours: i64,
+    /// Custom fields of test definition
+    #[serde(default)]
+    pub definition_fields: Vec<fields::DefinitionField>,
+    /// Minutes before end of planned test duration for overrun warning
+    #[serde(default = "Config::default_overrun_warning_minutes")]
+    pub overrun_warning_minutes: i64,
+    /// Language of stand and operator profiles, in config folder of stand
+    #[serde(default = "Config::default_language_cfg")]
+    pub language_cfg: path::PathBuf,
+    /// Folder of message catalogs `<language>.yaml` in settings folder
+    #[serde(default = "Config::default_language_dir")]
+    pub language_dir: path::PathBuf,
+    /// Name of log files, see `logfile::LogTemplate`
+    #[serde(default = "Config::default_log_file_template")]
+    pub log_file_template: String,
+    /// Age in days of log files moved to monthly archives at start, no archival if not set
+    #[serde(default)]
+    pub archive_after_days: Option<i64>,
 }
 
 impl Config {
@@ -517,9 +1046,12 @@ impl Config {
     pub fn new(stand_nm: u8) -> Result<Config, Box<dyn Error>> {
         let filename = PathBuf::from("madl.cfg");
         if filename.exists() {
-            return Ok(Config::read_config(filename)?)
+            let mut config = Config::read_config(filename)?;
+            config.stand_nm = stand_nm;
+            Ok(config)
         } else {
             let config = Config {
+                stand_nm,
                 settings_dir: PathBuf::from("C:\\Utilization Tool"),
                 teststand_dir: PathBuf::from(format!("Teststand{}", stand_nm)),
                 flag_dir: PathBuf::from("Utilization Flag"),
@@ -536,6 +1068,18 @@ impl Config {
                 temp_file: PathBuf::from("madl_temporary_file.txt"),
                 tc_root_folder: PathBuf::from("c:\\TCRoot"),
                 tc_log_folder: PathBuf::from(format!("station{}\\logs", stand_nm)),
+                interlock_source: InterlockSource::None,
+                tr_registry_cfg: Config::default_tr_registry_cfg(),
+                mqtt: None,
+                storage: storage::StorageBackend::Text,
+                share_dir: None,
+                pending_expiry_hours: Config::default_pending_expiry_hours(),
+                definition_fields: Vec::new(),
+                overrun_warning_minutes: Config::default_overrun_warning_minutes(),
+                language_cfg: Config::default_language_cfg(),
+                language_dir: Config::default_language_dir(),
+                log_file_template: Config::default_log_file_template(),
+                archive_after_days: None,
             };
             let f = fs::OpenOptions::new().write(true).create(true).open(&filename)?;
             serde_yaml::to_writer(f, &config)?;
@@ -543,6 +1087,30 @@ impl Config {
         }
     }
 
+    fn default_tr_registry_cfg() -> path::PathBuf {
+        PathBuf::from("TR Registry.cfg")
+    }
+
+    fn default_pending_expiry_hours() -> i64 {
+        24
+    }
+
+    fn default_overrun_warning_minutes() -> i64 {
+        30
+    }
+
+    fn default_language_cfg() -> path::PathBuf {
+        PathBuf::from("Language.cfg")
+    }
+
+    fn default_language_dir() -> path::PathBuf {
+        PathBuf::from("Utilization Language")
+    }
+
+    fn default_log_file_template() -> String {
+        logfile::DEFAULT_TEMPLATE.to_string()
+    }
+
     fn read_config(path: PathBuf) -> Result<Config, Box<dyn Error>> {
         let file = File::open(&path)?;
         let reader = io::BufReader::new(file);
@@ -569,16 +1137,63 @@ impl Config {
             .join(&self.log_dir)
     }
 
-    /// Get log file path with date filename
-    pub fn get_log_file_path(&self, date: DateTime<Local>) -> Result<path::PathBuf, Box<dyn Error>> {
-        let dir_path = self.get_log_dir_path();
+    /// Configuration of every stand with log folder in settings folder
+    pub fn all_stands(&self) -> Result<Vec<Config>, Box<dyn Error>> {
+        let mut out = Vec::new();
+        for entry in fs::read_dir(&self.settings_dir)? {
+            let path = entry?.path();
+            if !path.join(&self.log_dir).is_dir() {
+                continue;
+            }
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let digits: String = name.chars().rev().take_while(|c| c.is_ascii_digit()).collect();
+            let stand_nm = digits.chars().rev().collect::<String>().parse().unwrap_or(0);
+            let mut config = self.clone();
+            config.teststand_dir = PathBuf::from(name);
+            config.stand_nm = stand_nm;
+            out.push(config);
+        }
+        out.sort_by_key(|c| c.stand_nm);
+        Ok(out)
+    }
+
+    /// Return path to flag folder
+    pub fn get_flag_dir_path(&self) -> path::PathBuf {
+        self.settings_dir
+            .join(&self.teststand_dir)
+            .join(&self.flag_dir)
+    }
+
+    /// Log folder of stand on share, `None` without share
+    pub fn get_share_log_dir_path(&self) -> Option<path::PathBuf> {
+        self.share_dir.as_ref().map(|share| share.join(&self.teststand_dir).join(&self.log_dir))
+    }
+
+    /// Return path to folder with text waiting for unavailable share
+    pub fn get_spool_dir_path(&self) -> path::PathBuf {
+        self.settings_dir
+            .join(&self.teststand_dir)
+            .join("Utilization Spool")
+    }
+
+    /// Test bench id, first value from test bench id config file
+    pub fn get_test_bench_id(&self) -> Result<String, Box<dyn Error>> {
         let config_path = self.get_config_file_path(&self.test_bench_id_cfg);
         let test_bench_id_config_str = read_text_file(&config_path)?;
-        let test_bench_id = &parse_config(&test_bench_id_config_str)[0][0];
+        Ok(parse_config(&test_bench_id_config_str)[0][0].to_owned())
+    }
 
-        let date_string = date.format("%d%m%y").to_string();
+    /// Template of log file names
+    pub fn log_template(&self) -> Result<logfile::LogTemplate, Box<dyn Error>> {
+        logfile::LogTemplate::new(&self.log_file_template)
+    }
 
-        let filename = format!("{}_{}.txt", test_bench_id, date_string);
+    /// Get path of log file with given day from log file template
+    pub fn get_log_file_path(&self, date: DateTime<Local>) -> Result<path::PathBuf, Box<dyn Error>> {
+        let dir_path = self.get_log_dir_path();
+        let test_bench_id = self.get_test_bench_id()?;
+
+        let filename = self.log_template()?.file_name(&test_bench_id, date.date_naive());
         let log_file_path = dir_path.join(filename);
         //println!("Log file path: {:?}", log_file_path);
         Ok(log_file_path)
@@ -589,132 +1204,192 @@ impl Config {
     }
 }
 
-/// Confirm inserted data for request definition.
-fn confirm_output_info(output: &mut HashMap<&str, String>) -> Result<String, Box<dyn Error>> {
-    println!("TR number: {},", output.entry(&"TR_Number").or_insert("".to_string()));
-    println!("Specimen ID: {},", output.entry(&"Specimen ID").or_insert("".to_string()));
-    println!("Request type: {},", output.entry(&"Test Request type").or_insert("".to_string()));
-    println!("Test category: {}", output.entry(&"Testing_Category").or_insert("".to_string()));
-    println!("Operator: {}", output.entry(&"Technician").or_insert("".to_string()));
-    loop {
-        print!("\nConfirm data Yes/No >>");
-        let mut str_input = String::new();
-        io::stdout().flush()?;
-        io::stdin().read_line(&mut str_input)
-            .expect("Failed to read.");
-        match str_input.trim().to_lowercase().as_ref() {
-            "y" | "yes" => return Ok(str_input),
-            "n" | "no" => return Ok(str_input),
-            _ => {
-                println!("Inserted wrong value, please insert again!");
-                continue;
-            },
-        };
-    }
-}
-
-/// Append string to a file
-fn append_file(path: PathBuf, text: String) -> () {
+/// Append text to file, returns after text is written to disk
+fn append_file(path: PathBuf, text: String) -> Result<(), Box<dyn Error>> {
     let display = path.display();
     let mut file = match fs::OpenOptions::new().append(true).create(true).open(&path) {
-        Err(why) => panic!("couldn't create {}: {}", display, why.description()),
+        Err(why) => return Err(From::from(format!("couldn't create {}: {}", display, why))),
         Ok(file) => file,
     };
 
-    match file.write_all(text.as_bytes()) {
-        Err(why) => panic!("couldn't write to {}: {}", display, why.description()),
-        Ok(_) => return (),
+    if let Err(why) = file.write_all(text.as_bytes()).and_then(|_| file.sync_all()) {
+        return Err(From::from(format!("couldn't write to {}: {}", display, why)));
     }
+    Ok(())
 }
 
-/// Get user input for test definition
-pub fn user_inputs<'a>(config: &Config, mut output: HashMap<&'a str, String>) -> Result<HashMap<&'a str, String>, Box<dyn Error>> {
-
-    println!("\nUse previous values?:");
-    let answer = confirm_output_info(&mut output)?;
-    let answer = answer.trim().to_lowercase().clone();
-    match  answer.as_ref() {
-        "yes" | "y" => {
-            return Ok(output)},
-        _ => (),
+/// Remove incomplete last line of interrupted write from file, the line is kept in `torn_dir`.
+/// Returns removed line.
+pub fn repair_torn_line(path: &path::Path, torn_dir: &path::Path) -> Result<Option<String>, Box<dyn Error>> {
+    let contents = fs::read(path)?;
+    if contents.is_empty() || contents.ends_with(b"\n") {
+        return Ok(None);
     }
+    let end = contents.iter().rposition(|b| *b == b'\n').map_or(0, |pos| pos + 1);
+    let torn = String::from_utf8_lossy(&contents[end..]).to_string();
+
+    // Line is saved before it is removed from log
+    fs::create_dir_all(torn_dir)?;
+    append_file(torn_dir.join(path.file_name().unwrap()), format!("{}\r\n", torn))?;
+    let file = fs::OpenOptions::new().write(true).open(path)?;
+    file.set_len(end as u64)?;
+    file.sync_all()?;
+    Ok(Some(torn))
+}
 
-    loop {
-
-        let mut str_input = String::new();
-        println!("Write TR number:");
-        print!(">>");
-        io::stdout().flush()?;
-        io::stdin().read_line(&mut str_input)
-            .expect("Failed to read TR number");
-        output.insert(&"TR_Number", str_input.trim().to_string());
-
-        let mut str_input = String::new();
-        println!("\nWrite Specimen ID:");
-        print!(">>");
-        io::stdout().flush()?;
-        io::stdin().read_line(&mut str_input)
-                .expect("Failed to read Specimen ID");
-        output.insert(&"Specimen ID", str_input.trim().to_string());
+/// Check log files of stand for incomplete last line after crash or power loss
+pub fn repair_torn_logs(config: &Config) -> Result<(), Box<dyn Error>> {
+    let torn_dir = config.get_log_dir_path().join("MADL torn lines");
+    for path in logfile::log_files(config)? {
+        // Other process can be appending record to the file
+        let _lock = logfile::LogLock::acquire(config)?;
+        if let Some(torn) = repair_torn_line(&path, &torn_dir)? {
+            eprintln!("Incomplete last line removed from {}: {}\nLine is kept in: {}", path.display(), torn, torn_dir.display());
+        }
+    }
+    Ok(())
+}
 
-        println!("\nChoose request type:");
-        let path = config.get_config_file_path(&config.test_request_type_cfg);
-        let test_request = TestInfo::new(&path)?;
-        output.insert(&"Test Request type", test_request.choose_value()?);
+/// Write test definition to log file
+pub fn write_test_definition(config: &Config, definition: &TestDefinition) -> Result<(), Box<dyn Error>> {
+    let origin = Origin::new(config)?;
+    let interlock = interlock::read_interlock(config)?;
+    for storage in storage::open(config, Local::now())?.iter_mut() {
+        storage.write_definition(definition, &origin, interlock)?;
+    }
 
-        println!("\nChoose test category:");
-        let path = config.get_config_file_path(&config.test_category_cfg);
-        let test_category = TestCategory::new(&path)?;
-        let (category, time) = test_category.choose_value()?;
-        output.insert("Testing_Category", category.to_owned());
-        output.insert(&"Available Time", time.to_owned());
+    Ok(())
+}
 
-        println!("\nChoose operator:");
-        let path = config.get_config_file_path(&config.operator_list_cfg);
-        let test_operator = TestInfo::new(&path)?;
-        output.insert(&"Technician", test_operator.choose_value()?);
+/// Write removal of failed specimen from running test, test continues with other specimens
+pub fn remove_specimen(config: &Config, specimen: &str, reason: &str) -> Result<(), Box<dyn Error>> {
+    let state = read_last_state(config)?;
+    match check_state(&state) {
+        Laststate::IN(vec_data) if vec_data[0].contains("Test Start") => (),
+        _ => return Err(From::from("No test is running")),
+    }
+    let specimens = state.definition.specimens();
+    if !specimens.iter().any(|s| s.id == specimen) {
+        return Err(From::from(format!("Specimen {} is not in running test, specimens: {}", specimen, state.definition.specimen_id)));
+    }
+    // Definition of running test can be in log file of previous day
+    let mut removed = report::removed_specimens(&read_history(config)?)?;
+    if removed.iter().any(|s| s == specimen) {
+        return Err(From::from(format!("Specimen {} is already removed", specimen)));
+    }
 
-        println!("\nCheck values:");
-        let answer = confirm_output_info(&mut output)?;
-        let answer = answer.trim().to_lowercase().clone();
-        match  answer.as_ref() {
-            "yes" | "y" => break,
-            "no" | "n" => continue,
-            _ => continue,
-        }
+    write_log_line(config, vec!("SPECIMEN", "Removed", specimen, reason))?;
+    println!("{}", lang::format("specimen_removed", &[&specimen]));
+    removed.push(specimen.to_string());
+    if specimens.iter().all(|s| removed.contains(&s.id)) {
+        println!("{}", lang::text("all_specimens_removed"));
     }
-    Ok(output)
+    Ok(())
 }
 
-/// Write test definition to log file
-pub fn write_test_definition(config: &Config, output: &HashMap<&str, String>) -> Result<(), Box<dyn Error>> {
-    let text = format_output(&output);
-    let local: DateTime<Local> = Local::now();
-    let filename = config.get_log_file_path(local)?;
-    append_file(filename, text);
+/// Append record in current log version to log file.
+/// `previous` is content of previous log file, interval closed by OUT record can start there.
+/// `last_written` is content of other log file with last written record, new record is linked to it.
+/// Returns record with assigned id, duration and hash and appended text.
+fn append_record(path: PathBuf, mut record: record::Record, previous: Option<&str>, last_written: Option<&str>) -> Result<(record::Record, String), Box<dyn Error>> {
+    let contents = if path.exists() {
+        read_text_file(&path)?
+    } else {
+        String::new()
+    };
+    let lines = record::parse_lines(&contents)?;
+    let previous_lines = previous.and_then(|text| record::parse_lines(text).ok());
+    let mut out_text = String::new();
+    if record::last_version(&lines) != record::MADL_VERSION {
+        out_text.push_str(&format!("MADL_Version::{}\r\n", record::MADL_VERSION));
+    }
 
-    Ok(())
+    record.tags.id = Some(record::next_id(&lines));
+    if record.kind == record::RecordKind::Out {
+        let start = match (record::has_records(&lines), previous_lines.as_deref()) {
+            (false, Some(previous)) => record::open_in(previous),
+            _ => record::open_in(&lines),
+        };
+        record.tags.duration = start.map(|start| (record.time - start.time).num_seconds());
+    }
+    if record.kind == record::RecordKind::Resume {
+        let pause = match (record::has_records(&lines), previous_lines.as_deref()) {
+            (false, Some(previous)) => record::open_pause(previous),
+            _ => record::open_pause(&lines),
+        };
+        record.tags.duration = pause.map(|pause| (record.time - pause.time).num_seconds());
+    }
+    let previous_record = last_written.and_then(record::last_record_line).or_else(|| record::last_record_line(&contents));
+    let mut lines_before = record::lines_since_record(&contents);
+    if !out_text.is_empty() {
+        lines_before.push(out_text.trim_end());
+    }
+    record.tags.prev = Some(record::link_hash(previous_record, &lines_before));
+    out_text.push_str(&record.to_line(record::MADL_VERSION));
+    out_text.push_str("\r\n");
+    append_file(path, out_text.clone())?;
+
+    Ok((record, out_text))
 }
 
 fn write_log_line(config: &Config, text_vec: Vec<&str>) -> Result<(), Box<dyn Error>> {
+    write_log_record(config, text_vec, None)
+}
+
+/// Write record with optional comment of operator
+fn write_log_record(config: &Config, text_vec: Vec<&str>, comment: Option<&str>) -> Result<(), Box<dyn Error>> {
     let local: DateTime<Local> = Local::now();
-    let date_string = local.format("%d/%m/%Y %H:%M:%S").to_string();
-    let mut text_iter = text_vec.iter();
-    let mut out_text: String = String::new();
+    let kind = match record::parse_kind(text_vec[0]) {
+        Some(kind) => kind,
+        None => return Err(From::from(format!("Unknown record: {}", text_vec[0]))),
+    };
+    let record = record::Record {
+        kind,
+        time: local.fixed_offset(),
+        fields: text_vec[1..].iter().map(|s| s.to_string()).collect(),
+        tags: record::Tags {
+            origin: Some(Origin::new(config)?),
+            comment: comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()),
+            ..Default::default()
+        },
+    };
 
-    out_text.push_str(text_iter.next().unwrap());
-    out_text.push_str("::");
-    out_text.push_str(&date_string);
-    for i in text_iter {
-        out_text.push_str("::");
-        out_text.push_str(i);
+    let mut record = record;
+    for storage in storage::open(config, local)?.iter_mut() {
+        record = storage.append_record(record)?;
     }
-    out_text.push_str("\r\n");
+    #[cfg(feature = "mqtt")]
+    if let Err(e) = mqtt::publish_record(&record) {
+        eprintln!("State not published to MQTT: {}", e);
+    }
+    Ok(())
+}
 
-    let filename = config.get_log_file_path(local)?;
-    append_file(filename, out_text);
+/// Read interlock state and write INTERLOCK record if it changed from last logged state
+pub fn track_interlock(config: &Config) -> Result<Interlock, Box<dyn Error>> {
+    let (state, changed) = update_interlock(config)?;
+    if changed {
+        println!("\n{}", lang::format("interlock_changed", &[&state]));
+    }
+    Ok(state)
+}
 
-    Ok(())
+/// Read interlock state and log it if changed, return state and if it was changed
+fn update_interlock(config: &Config) -> Result<(Interlock, bool), Box<dyn Error>> {
+    let state = interlock::read_interlock(config)?;
+    // Interlock can be logged in log file of previous day
+    let mut last = None;
+    for path in logfile::log_files(config).unwrap_or_default().iter().rev() {
+        last = record::last_interlock(&record::parse_lines(&read_text_file(path)?)?);
+        if last.is_some() {
+            break;
+        }
+    }
+    let changed = last.as_deref().and_then(Interlock::parse) != Some(state);
+    if changed {
+        write_log_line(config, vec!("INTERLOCK", &state.to_string()))?;
+    }
+    Ok((state, changed))
 }
 
 // Write TR specification and start time of testing.
@@ -722,14 +1397,26 @@ pub fn write_test_start(config: &Config) -> Result<(), Box<dyn Error>> {
     write_log_line(config, vec!("IN", "Test Start"))
 }
 
-/// Write test continue log line
-pub fn write_continue(config: &Config) -> Result<(), Box<dyn Error>> {
-    write_log_line(config, vec!("OUT", "Test Stopped", "Select", "Running Continuous"))
+/// Write pause of running test with optional time loss classification
+pub fn write_pause(config: &Config, reason: Option<Vec<String>>) -> Result<(), Box<dyn Error>> {
+    let mut text_vec = vec!("PAUSE", "Test Paused");
+    if let Some(reason) = &reason {
+        text_vec.extend(reason.iter().map(|s| s.as_str()));
+    }
+    write_log_line(config, text_vec)
+}
+
+/// Write resume of paused test
+pub fn write_resume(config: &Config) -> Result<(), Box<dyn Error>> {
+    write_log_line(config, vec!("RESUME", "Test Resumed"))
 }
 
 /// Write test completed log line
-pub fn write_test_end(config: &Config, reason: String) -> Result<(), Box<dyn Error>> {
-    write_log_line(config, vec!("OUT", "Test Stopped", &reason, "none"))
+pub fn write_test_end(config: &Config, reason: &StopReason, comment: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let fields = reason.fields();
+    let mut text_vec = vec!("OUT", "Test Stopped");
+    text_vec.extend(fields.iter().map(|s| s.as_str()));
+    write_log_record(config, text_vec, comment)
 }
 
 /// Write test completed log line
@@ -738,8 +1425,8 @@ pub fn write_missing_test_end(config: &Config) -> Result<(), Box<dyn Error>> {
 }
 
 /// Write test loss start log line
-pub fn write_test_loss(config: &Config, data: Vec<String>) -> Result<(), Box<dyn Error>> {
-    write_log_line(config, vec!("IN", &data[0], &data[1], &data[2]))
+pub fn write_test_loss(config: &Config, data: Vec<String>, comment: Option<&str>) -> Result<(), Box<dyn Error>> {
+    write_log_record(config, vec!("IN", &data[0], &data[1], &data[2]), comment)
 }
 
 /// Write test loss end log line
@@ -747,74 +1434,220 @@ pub fn write_test_loss_end(config: &Config, data: &Vec<String>) -> Result<(), Bo
     write_log_line(config, vec!("OUT", &data[0], &data[1], &data[2]))
 }
 
-fn test_end_input(config: &Config) -> Result<String, Box<dyn Error>> {
-    println!("\nChoose test end reason:");
-    let path = config.get_config_file_path(&config.test_stop_reason_list_cfg);
-    let test_end = TestInfo::new(&path)?;
-    let out = test_end.choose_value()?;
+/// Resume paused test, returns false if test is not paused
+pub fn resume_test(config: &Config) -> Result<bool, Box<dyn Error>> {
+    match check_state(&read_last_state(config)?) {
+        Laststate::PAUSE(_) => {
+            write_resume(config)?;
+            Ok(true)
+        },
+        _ => Ok(false),
+    }
+}
 
-    Ok(out)
+/// Print report of intervals from log file of given day
+pub fn print_report(config: &Config, date: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+    let path = config.get_log_file_path(date)?;
+    if !path.exists() {
+        println!("Log file not exist: {:?}", path);
+        return Ok(());
+    }
+    let contents = read_text_file(&path)?;
+    println!("\nReport from log: {}\n", path.display());
+    if let Some(bench) = Origin::bench_from_path(config, &path) {
+        for warning in report::verify_origin(&contents, &bench, config.stand_nm) {
+            println!("Warning: {}", warning);
+        }
+    }
+    let (from, to) = match logfile::parse_name(config, &path) {
+        Some(name) => name.bounds(),
+        None => return Err(From::from(format!("Log file name does not match template: {}", path.display()))),
+    };
+    println!("{}", report::report(&log_history(config)?, from, to, record::now()));
+    Ok(())
 }
 
-pub fn end_of_test(config: &Config, testloss_skip: bool) -> Result<bool, Box<dyn Error>> {
+/// Read index of chosen value from list with given lenght
+fn read_index(lenght: usize) -> Result<usize, Box<dyn Error>> {
     loop {
-        println!("\nEnd of test or continue? (End/Con):");
-        print!(">>");
         let mut str_input = String::new();
+        print!(">>");
         io::stdout().flush()?;
         io::stdin().read_line(&mut str_input)
-            .expect("Failed to read.");
-        let answer = str_input;
-        let answer = answer.trim().to_lowercase().clone();
-        let _res = match  answer.as_ref() {
-            "con" | "c" => {
-                write_continue(&config).unwrap();
-                return Ok(true)
-            },
-            "end" | "e" => {
-                let end_reson = test_end_input(&config)?;
-                write_test_end(&config, end_reson)?;
-                if !testloss_skip {
-                    let out = testloose_inputs(&config)?;
-                    write_test_loss(&config, out)?;
-                }
-                return Ok(false)
-            },
+            .expect("Failed to read input.");
+        match str_input.trim().parse::<usize>() {
+            Ok(num) if num < lenght => return Ok(num),
             _ => {
-                println!("Inserted wrong value, please insert again!");
+                println!("{}\n", lang::format("wrong_value", &[&str_input.trim()]));
                 continue;
             },
         };
     }
 }
 
-pub fn testloose_inputs(config: &Config) -> Result<Vec<String>, Box<dyn Error>> {
-
+/// Read split times of interval from user, times are sorted and inside of interval
+fn split_times_input(start: record::LogTime, end: record::LogTime) -> Result<Vec<record::LogTime>, Box<dyn Error>> {
+    let mut out: Vec<record::LogTime> = Vec::new();
     loop {
-        println!("\nChoose time loss clasification:");
-        let path = config.get_config_file_path(&config.timeloss_classification_cfg);
-        let test_request = TestLossClass::new(&path)?;
-        let testclass = test_request.choose_value()?;
-
-        println!("\nCheck values:");
-        println!("Time loss classification: {}", testclass[0]);
-        println!("Time loss sub classification: {}", testclass[1]);
-        println!("Time loss sub category: {}", testclass[0]);
-
+        println!("\n{}", lang::text("write_split_time"));
+        print!(">>");
         let mut str_input = String::new();
-        print!("\nConfirm data Yes/No >>");
         io::stdout().flush()?;
         io::stdin().read_line(&mut str_input)
-            .expect("Failed to read.");
-        let answer = str_input.trim().to_lowercase().clone();
-        match  answer.as_ref() {
-            "yes" | "y" => {
-                return Ok(testclass);
+            .expect("Failed to read split time");
+        let text = str_input.trim();
+        if text.is_empty() {
+            return Ok(out);
+        }
+        let time = match NaiveTime::parse_from_str(text, "%H:%M:%S") {
+            Ok(time) => time,
+            Err(e) => {
+                println!("{}\n{:?}", lang::format("wrong_time", &[&text]), e);
+                continue;
+            },
+        };
+        // Interval can continue over midnight
+        let mut naive = start.date_naive().and_time(time);
+        if naive <= start.naive_local() {
+            naive += chrono::Duration::days(1);
+        }
+        let last = *out.last().unwrap_or(&start);
+        let split = record::resolve_local(naive, &Local, Some(last));
+        if split <= last || split >= end {
+            println!("{}", lang::format("split_time_range", &[&last.format(LOG_TIME_FORMAT), &end.format(LOG_TIME_FORMAT)]));
+            continue;
+        }
+        out.push(split);
+    }
+}
+
+/// Split logged time loss interval into several classified parts written as SPLIT records
+pub fn split_test_loss(config: &Config, date: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+    let path = config.get_log_file_path(date)?;
+    if !path.exists() {
+        println!("Log file not exist: {:?}", path);
+        return Ok(());
+    }
+    let contents = read_text_file(&path)?;
+    let intervals: Vec<report::Interval> = report::parse_log(&contents)?.intervals
+        .into_iter()
+        .filter(|i| i.is_loss())
+        .collect();
+    if intervals.is_empty() {
+        println!("{}", lang::format("no_time_loss_interval", &[&path.display()]));
+        return Ok(());
+    }
+
+    println!("\n{}", lang::text("choose_split_interval"));
+    for (i, interval) in intervals.iter().enumerate() {
+        println!("{}. {}", i, interval);
+    }
+    let interval = &intervals[read_index(intervals.len())?];
+    let now = record::now();
+    let end = interval.end.unwrap_or(now);
+
+    let mut starts = vec!(interval.start);
+    starts.extend(split_times_input(interval.start, end)?);
+    if starts.len() < 2 {
+        println!("{}", lang::text("no_split_time"));
+        return Ok(());
+    }
+
+    let class_path = config.get_config_file_path(&config.timeloss_classification_cfg);
+    let loss_class = TestLossClass::new(&class_path)?;
+    let origin = Origin::new(config)?;
+    let mut records = Vec::new();
+    for (i, start) in starts.iter().enumerate() {
+        let part_end = starts.get(i + 1).unwrap_or(&end);
+        println!("\n{}", lang::format("split_classification", &[&start.format(LOG_TIME_FORMAT), &part_end.format(LOG_TIME_FORMAT)]));
+        let class = loss_class.choose_value()?;
+        records.push(report::split_record(now, interval.start, *start, class, origin.clone()));
+    }
+    let mut storages = storage::open(config, date)?;
+    for record in records {
+        let mut record = record;
+        for storage in storages.iter_mut() {
+            record = storage.append_record(record)?;
+        }
+    }
+    println!("\n{}", lang::format("interval_split", &[&starts.len()]));
+
+    Ok(())
+}
+
+/// Check hash chain of records in archived and current log files of stand, error with first broken link
+pub fn verify_logs(config: &Config) -> Result<(), Box<dyn Error>> {
+    let contents = logfile::read_logs(config)?;
+    let count = record::verify_chain(&contents)?;
+    println!("Hash chain verified: {} records in {} log files", count, contents.len());
+    Ok(())
+}
+
+/// Content of archived and current log files of stand joined in order of dates,
+/// intervals started before midnight are closed in log file of next day
+pub fn read_history(config: &Config) -> Result<String, Box<dyn Error>> {
+    let mut contents = String::new();
+    for (_, text) in logfile::read_logs(config)? {
+        contents.push_str(&text);
+        if !contents.ends_with('\n') {
+            contents.push_str("\r\n");
+        }
+    }
+    Ok(contents)
+}
+
+/// Parsed archived and current log files of stand, intervals, specimen periods
+/// and test runs continue over midnight
+pub fn log_history(config: &Config) -> Result<report::LogContent, Box<dyn Error>> {
+    report::parse_log(&read_history(config)?)
+}
+
+/// Convert log files of older MADL version to current version.
+/// Original files are kept in subfolder of log folder.
+pub fn migrate_logs(config: &Config) -> Result<(), Box<dyn Error>> {
+    let log_dir = config.get_log_dir_path();
+    let backup_dir = log_dir.join("MADL original logs");
+    let mut count = 0;
+
+    for path in logfile::log_files(config)? {
+        let contents = match read_text_file(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                println!("Skipped {}: {}", path.display(), e);
+                continue;
+            },
+        };
+        let lines = match record::parse_lines(&contents) {
+            Ok(lines) => lines,
+            Err(e) => {
+                println!("Skipped {}: {}", path.display(), e);
+                continue;
             },
-            "no" | "n" => continue,
-            _ => continue,
+        };
+        // File without version header is not MADL log
+        if !lines.iter().any(|l| matches!(l, record::Line::Version(_))) {
+            println!("Skipped {}: no MADL_Version line", path.display());
+            continue;
         }
+        if !record::needs_migration(&lines) {
+            continue;
+        }
+
+        fs::create_dir_all(&backup_dir)?;
+        let backup = backup_dir.join(path.file_name().unwrap());
+        if !backup.exists() {
+            fs::copy(&path, &backup)?;
+        }
+        // Keep modification time of original log
+        let modified = fs::metadata(&path)?.modified()?;
+        fs::write(&path, record::migrate(&contents)?)?;
+        File::options().write(true).open(&path)?.set_modified(modified)?;
+        println!("Migrated: {}", path.display());
+        count += 1;
     }
+    println!("\nMigrated {} log files to MADL_Version {}, originals in: {}", count, record::MADL_VERSION, backup_dir.display());
+
+    Ok(())
 }
 
 pub enum TcState {
@@ -857,12 +1690,14 @@ pub fn read_tc_log(path: PathBuf) -> TcState {
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_config() {
-        let config = Config {
-            settings_dir: PathBuf::from("C:\\Utilization Tool"),
-            teststand_dir: PathBuf::from(format!("Teststand{}", 1)),
+    use proptest::prelude::*;
+
+    /// Configuration of stand 1 with all folders in `settings_dir`
+    pub(crate) fn stand_config(settings_dir: PathBuf) -> Config {
+        Config {
+            stand_nm: 1,
+            settings_dir: settings_dir.clone(),
+            teststand_dir: PathBuf::from("Teststand1"),
             flag_dir: PathBuf::from("Utilization Flag"),
             log_dir: PathBuf::from("Utilization Log"),
             config_dir: PathBuf::from("Utilization Config"),
@@ -875,10 +1710,230 @@ mod tests {
             user_data_cfg: PathBuf::from("User data.cfg"),
             user_preference_cfg: PathBuf::from("User preference.cfg"),
             temp_file: PathBuf::from("madl_temporary_file.txt"),
-            tc_root_folder: PathBuf::from("c:\\TCRoot"),
-            tc_log_folder: PathBuf::from(format!("station{}\\logs", 1)),
-        };
+            tc_root_folder: settings_dir.join("TCRoot"),
+            tc_log_folder: PathBuf::from("logs"),
+            interlock_source: InterlockSource::None,
+            tr_registry_cfg: PathBuf::from("TR Registry.cfg"),
+            mqtt: None,
+            storage: storage::StorageBackend::Text,
+            share_dir: None,
+            pending_expiry_hours: 24,
+            overrun_warning_minutes: 30,
+            definition_fields: Vec::new(),
+            language_cfg: PathBuf::from("Language.cfg"),
+            language_dir: PathBuf::from("Utilization Language"),
+            log_file_template: logfile::DEFAULT_TEMPLATE.to_string(),
+            archive_after_days: None,
+        }
+    }
+
+    /// Stand with config files and test bench TB1 in new folder of temp dir, removed by caller
+    pub(crate) fn temp_stand(name: &str) -> Config {
+        let dir = env::temp_dir().join(format!("madl_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = stand_config(dir);
+        create_config_files(&config);
+        fs::write(config.get_config_file_path(&config.test_bench_id_cfg), "TB1").unwrap();
+        config
+    }
+
+    #[test]
+    fn test_config() {
+        let config = stand_config(PathBuf::from("C:\\Utilization Tool"));
         assert_eq!(config.get_config_file_path(&config.operator_list_cfg), PathBuf::from("C:\\Utilization Tool\\Teststand1\\Utilization Config\\Operator List.cfg"));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_stop_reason() {
+        let reason = StopReason::parse(" Finished/Specimen failure*fail").unwrap();
+        assert_eq!(reason.reason, "Finished");
+        assert_eq!(reason.sub_reason.as_deref(), Some("Specimen failure"));
+        assert_eq!(reason.fields(), vec!("Finished", "Specimen failure", "fail"));
+        // Flat reason of older config
+        let flat = StopReason::parse("Finished").unwrap();
+        assert_eq!(flat.fields(), vec!("Finished", "none"));
+        assert!(StopReason::parse("Finished*maybe").is_err());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_config_round_trip(values in prop::collection::vec(any::<String>(), 1..5)) {
+            let line = values.iter().map(|v| escape_config(v)).collect::<Vec<_>>().join(",");
+            prop_assume!(!line.is_empty());
+            prop_assert_eq!(parse_config(&format!("{}\r\nnext, line", line)), vec!(values, vec!("next".to_string(), " line".to_string())));
+        }
+
+        #[test]
+        fn prop_log_round_trip(tr_number in any::<String>(), technician in any::<String>(), loss in any::<String>()) {
+            let config = temp_stand("escape");
+            let dir = config.settings_dir.clone();
+
+            let definition = TestDefinition { tr_number, technician, ..Default::default() };
+            write_test_definition(&config, &definition).unwrap();
+            write_log_line(&config, vec!("IN", "Idle Time", &loss)).unwrap();
+            let state = get_log_data(config.get_log_file_path(Local::now()).unwrap(), &[]).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+            prop_assert_eq!((&state.definition.tr_number, &state.definition.technician), (&definition.tr_number, &definition.technician));
+            match check_state(&state) {
+                Laststate::IN(fields) => prop_assert_eq!(fields, vec!("Idle Time".to_string(), loss)),
+                _ => prop_assert!(false, "Last record is not IN: {}", state.last_line),
+            }
+        }
+    }
+
+    #[test]
+    fn test_pause_next_day() {
+        let config = temp_stand("pause");
+        let definition = TestDefinition { tr_number: "TR1".to_string(), technician: "John".to_string(), ..Default::default() };
+        write_test_definition(&config, &definition).unwrap();
+        write_test_start(&config).unwrap();
+        // Test started yesterday continues in log file of today
+        let today = config.get_log_file_path(Local::now()).unwrap();
+        fs::rename(&today, config.get_log_file_path(Local::now() - Duration::days(1)).unwrap()).unwrap();
+
+        write_pause(&config, None).unwrap();
+        let state = read_last_state(&config).unwrap();
+        assert!(matches!(check_state(&state), Laststate::PAUSE(_)));
+        assert_eq!(state.definition.tr_number, "TR1");
+
+        write_resume(&config).unwrap();
+        write_log_line(&config, vec!("INTERLOCK", "Enabled")).unwrap();
+        let state = read_last_state(&config).unwrap();
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+        match check_state(&state) {
+            Laststate::IN(fields) => assert_eq!(fields[0], "Test Start"),
+            _ => panic!("Test is not running: {}", state.last_line),
+        }
+        assert_eq!((state.definition.tr_number.as_str(), state.definition.technician.as_str()), ("TR1", "John"));
+    }
+
+    #[test]
+    fn test_split_previous_day() {
+        let config = temp_stand("split");
+        write_test_start(&config).unwrap();
+        let yesterday = Local::now() - Duration::days(1);
+        let today = config.get_log_file_path(Local::now()).unwrap();
+        fs::rename(&today, config.get_log_file_path(yesterday).unwrap()).unwrap();
+        write_log_line(&config, vec!("INTERLOCK", "Enabled")).unwrap();
+
+        // Split of interval from yesterday is written to log file of yesterday
+        let now = record::now();
+        let split = report::split_record(now, now, now, vec!("A".to_string(), "B".to_string(), "C".to_string()), Origin::new(&config).unwrap());
+        for storage in storage::open(&config, yesterday).unwrap().iter_mut() {
+            storage.append_record(split.clone()).unwrap();
+        }
+        write_log_line(&config, vec!("INTERLOCK", "Disabled")).unwrap();
+        let result = record::verify_chain(&logfile::read_logs(&config).unwrap());
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+        assert_eq!(result.unwrap(), 4);
+    }
+
+    #[test]
+    fn test_concurrent_append() {
+        let config = temp_stand("concurrent");
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| for _ in 0..5 {
+                    write_log_line(&config, vec!("INTERLOCK", "Enabled")).unwrap();
+                });
+            }
+        });
+        let result = record::verify_chain(&logfile::read_logs(&config).unwrap());
+        let contents = read_text_file(&config.get_log_file_path(Local::now()).unwrap()).unwrap();
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+        assert_eq!(result.unwrap(), 20);
+        let ids: HashSet<u64> = record::parse_lines(&contents).unwrap().iter()
+            .filter_map(|line| match line {
+                record::Line::Record(record) => record.tags.id,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids.len(), 20);
+    }
+
+    #[test]
+    fn test_repair_torn_logs() {
+        let config = temp_stand("torn");
+        write_test_start(&config).unwrap();
+        let log = config.get_log_file_path(Local::now()).unwrap();
+        let contents = fs::read_to_string(&log).unwrap();
+        fs::write(&log, format!("{}OUT::01/02/2020 10:0", contents)).unwrap();
+        // Other files in log folder are not log files of stand
+        let notes = config.get_log_dir_path().join("notes.txt");
+        fs::write(&notes, "no newline").unwrap();
+
+        repair_torn_logs(&config).unwrap();
+        let repaired = fs::read_to_string(&log).unwrap();
+        let notes = fs::read_to_string(&notes).unwrap();
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+        assert_eq!(repaired, contents);
+        assert_eq!(notes, "no newline");
+    }
+
+    #[test]
+    fn test_migrate_logs() {
+        let config = temp_stand("migrate");
+        fs::create_dir_all(config.get_log_dir_path()).unwrap();
+        let day = Local.with_ymd_and_hms(2020, 2, 1, 12, 0, 0).unwrap();
+        let old = config.get_log_file_path(day).unwrap();
+        fs::write(&old, "MADL_Version::2.5\r\nIN::01/02/2020 08:00:00::Test Start\r\n").unwrap();
+        // Unreadable file and file without version header are kept as they are
+        let binary = config.get_log_file_path(day + Duration::days(1)).unwrap();
+        fs::write(&binary, [0xff, 0xfe, 0x00]).unwrap();
+        let plain = config.get_log_file_path(day + Duration::days(2)).unwrap();
+        fs::write(&plain, "some text\r\n").unwrap();
+
+        migrate_logs(&config).unwrap();
+        let migrated = fs::read_to_string(&old).unwrap();
+        let plain = fs::read_to_string(&plain).unwrap();
+        let binary = fs::read(&binary).unwrap();
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+        assert!(migrated.starts_with(&format!("MADL_Version::{}", record::MADL_VERSION)));
+        assert_eq!(plain, "some text\r\n");
+        assert_eq!(binary, vec!(0xff, 0xfe, 0x00));
+    }
+
+    #[test]
+    fn test_interrupted_writes() {
+        let dir = env::temp_dir().join(format!("madl_test_interrupted_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // Power loss during append of second record
+        let log = dir.join("TB1_010220.txt");
+        fs::write(&log, "MADL_Version::3.0\r\nIN::01/02/2020 08:00:00::Test Start::ID=1\r\nOUT::01/02/2020 10:0").unwrap();
+        let torn_dir = dir.join("torn");
+        assert_eq!(repair_torn_line(&log, &torn_dir).unwrap(), Some("OUT::01/02/2020 10:0".to_string()));
+        let contents = fs::read_to_string(&log).unwrap();
+        assert_eq!(contents, "MADL_Version::3.0\r\nIN::01/02/2020 08:00:00::Test Start::ID=1\r\n");
+        assert!(record::parse_lines(&contents).is_ok());
+        assert_eq!(fs::read_to_string(torn_dir.join("TB1_010220.txt")).unwrap(), "OUT::01/02/2020 10:0\r\n");
+        assert_eq!(repair_torn_line(&log, &torn_dir).unwrap(), None);
+
+        // Crash before rename leaves previous definition, shorter definition leaves no garbage
+        let deffile = DefFile { path: dir.join("definition.txt"), legacy_path: dir.join("legacy.txt"), expiry_hours: 24 };
+        let mut definition = TestDefinition { tr_number: "TR123456789".to_string(), ..Default::default() };
+        deffile.write_temp_output(&definition).unwrap();
+        fs::write(dir.join("definition.new"), "TR_Number: TR9\nSpeci").unwrap();
+        assert_eq!(deffile.read_temp_output(TestDefinition::default()).unwrap().tr_number, "TR123456789");
+        definition.tr_number = "TR1".to_string();
+        deffile.write_temp_output(&definition).unwrap();
+        assert_eq!(deffile.read_temp_output(TestDefinition::default()).unwrap(), definition);
+        assert!(!deffile.legacy_path.exists());
+
+        // Definition of version 1 in temp folder without some keys
+        deffile.remove_temp_file().unwrap();
+        fs::write(&deffile.legacy_path, "TR_Number: TR2\nlast_line: ''\nTechnician: John\n").unwrap();
+        let pending = deffile.read().unwrap().unwrap();
+        assert_eq!((pending.definition.tr_number.as_str(), pending.definition.technician.as_str()), ("TR2", "John"));
+        assert!(!deffile.is_expired(&pending));
+        assert_eq!(deffile.read_temp_output(definition).unwrap().specimen_id, "");
+        let pending = PendingDefinition { created: "01/02/2020 08:00:00".to_string(), ..pending };
+        assert!(deffile.is_expired(&pending));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+}
\ No newline at end of file
@@ -0,0 +1,454 @@
+use std::fs::{self, File};
+use std::io::{self, prelude::*};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{self, Instant};
+use chrono::{Datelike, Duration, Local, Months, NaiveDate, Weekday};
+use regex::Regex;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::SimpleFileOptions;
+
+use crate::Config;
+use crate::record::{self, LogTime};
+
+/// Template of daily log files `<bench>_<ddmmyy>.txt` of older versions
+pub const DEFAULT_TEMPLATE: &str = "{bench}_{dd}{mm}{yy}.txt";
+
+/// Folder of monthly archives in log folder
+pub const ARCHIVE_DIR: &str = "MADL archive";
+
+/// File in stand folder with name of log file with last written record
+const LAST_WRITTEN_FILE: &str = "madl_last_log.txt";
+
+/// Lock file in stand folder, see `LogLock`
+const LOCK_FILE: &str = "madl_log.lock";
+
+/// Lock is held only during append, older lock is left by crashed process
+const STALE_LOCK: time::Duration = time::Duration::from_secs(5);
+
+/// Time of waiting for lock held by other process
+const LOCK_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Bench,
+    Year,
+    ShortYear,
+    Month,
+    Week,
+    Day,
+}
+
+/// Time span of one log file, given by date placeholders of template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// File name template of log files with placeholders `{bench}`, `{yyyy}`, `{yy}`, `{mm}`, `{dd}`
+/// and ISO week `{ww}`. Year of template with week is ISO week year.
+#[derive(Debug, Clone)]
+pub struct LogTemplate {
+    parts: Vec<Part>,
+    pub period: Period,
+    pattern: Regex,
+}
+
+/// Period and test bench from log file name
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogName {
+    /// First day of period of log file
+    pub date: NaiveDate,
+    /// Last day of period of log file
+    pub end: NaiveDate,
+    pub bench: Option<String>,
+}
+
+impl LogName {
+    /// Start of first day and end of last day of period in local time
+    pub fn bounds(&self) -> (LogTime, LogTime) {
+        let midnight = |date: NaiveDate| record::resolve_local(date.and_hms_opt(0, 0, 0).unwrap(), &Local, None);
+        (midnight(self.date), midnight(self.end + Duration::days(1)))
+    }
+}
+
+impl LogTemplate {
+    pub fn new(template: &str) -> Result<LogTemplate, Box<dyn Error>> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err(From::from(format!("Unclosed placeholder in log file template: {}", template))),
+            };
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let part = match &rest[start + 1..end] {
+                "bench" => Part::Bench,
+                "yyyy" => Part::Year,
+                "yy" => Part::ShortYear,
+                "mm" => Part::Month,
+                "ww" => Part::Week,
+                "dd" => Part::Day,
+                other => return Err(From::from(format!("Unknown placeholder {{{}}} in log file template: {}", other, template))),
+            };
+            if parts.contains(&part) {
+                return Err(From::from(format!("Repeated placeholder in log file template: {}", template)));
+            }
+            parts.push(part);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        if parts.iter().any(|part| matches!(part, Part::Text(text) if text.contains(&['/', '\\', '}'][..]))) {
+            return Err(From::from(format!("Log file template is not file name: {}", template)));
+        }
+
+        let has = |part: Part| parts.contains(&part);
+        let year = has(Part::Year) != has(Part::ShortYear);
+        let period = match (year, has(Part::Month), has(Part::Week), has(Part::Day)) {
+            (true, true, false, true) => Period::Day,
+            (true, false, true, false) => Period::Week,
+            (true, true, false, false) => Period::Month,
+            (true, false, false, false) => Period::Year,
+            _ => return Err(From::from(format!("Log file template needs one year with month, month and day or week: {}", template))),
+        };
+
+        let mut pattern = String::from("^");
+        for part in parts.iter() {
+            pattern.push_str(&match part {
+                Part::Text(text) => regex::escape(text),
+                Part::Bench => "(?P<bench>.+?)".to_string(),
+                Part::Year => r"(?P<yyyy>\d{4})".to_string(),
+                Part::ShortYear => r"(?P<yy>\d{2})".to_string(),
+                Part::Month => r"(?P<mm>\d{2})".to_string(),
+                Part::Week => r"(?P<ww>\d{2})".to_string(),
+                Part::Day => r"(?P<dd>\d{2})".to_string(),
+            });
+        }
+        pattern.push('$');
+
+        Ok(LogTemplate { parts, period, pattern: Regex::new(&pattern)? })
+    }
+
+    /// Name of log file of test bench with given day
+    pub fn file_name(&self, bench: &str, date: NaiveDate) -> String {
+        let week = date.iso_week();
+        let year = if self.period == Period::Week { week.year() } else { date.year() };
+        self.parts.iter().map(|part| match part {
+            Part::Text(text) => text.clone(),
+            Part::Bench => bench.to_string(),
+            Part::Year => format!("{:04}", year),
+            Part::ShortYear => format!("{:02}", year.rem_euclid(100)),
+            Part::Month => format!("{:02}", date.month()),
+            Part::Week => format!("{:02}", week.week()),
+            Part::Day => format!("{:02}", date.day()),
+        }).collect()
+    }
+
+    /// Period and test bench of file name, `None` if name does not match template
+    pub fn parse(&self, file_name: &str) -> Option<LogName> {
+        let caps = self.pattern.captures(file_name)?;
+        let number = |name: &str| caps.name(name).and_then(|m| m.as_str().parse::<u32>().ok());
+        let year = match (number("yyyy"), number("yy")) {
+            (Some(year), _) => year as i32,
+            // Two digit year of older logs is in this century
+            (None, Some(year)) => 2000 + year as i32,
+            (None, None) => return None,
+        };
+        let (date, end) = match self.period {
+            Period::Day => {
+                let date = NaiveDate::from_ymd_opt(year, number("mm")?, number("dd")?)?;
+                (date, date)
+            },
+            Period::Week => {
+                let date = NaiveDate::from_isoywd_opt(year, number("ww")?, Weekday::Mon)?;
+                (date, date + Duration::days(6))
+            },
+            Period::Month => {
+                let date = NaiveDate::from_ymd_opt(year, number("mm")?, 1)?;
+                (date, date.checked_add_months(Months::new(1))? - Duration::days(1))
+            },
+            Period::Year => (NaiveDate::from_ymd_opt(year, 1, 1)?, NaiveDate::from_ymd_opt(year, 12, 31)?),
+        };
+        Some(LogName { date, end, bench: caps.name("bench").map(|m| m.as_str().to_string()) })
+    }
+}
+
+/// Period and test bench from name of log file. Files of default template are found after change of template.
+pub fn parse_name(config: &Config, path: &Path) -> Option<LogName> {
+    let name = path.file_name()?.to_str()?;
+    config.log_template().ok()?.parse(name)
+        .or_else(|| LogTemplate::new(DEFAULT_TEMPLATE).ok()?.parse(name))
+}
+
+/// Log files of stand matching template with their period, sorted by date
+fn dated_log_files(config: &Config) -> Result<Vec<(LogName, PathBuf)>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(config.get_log_dir_path())? {
+        let path = entry?.path();
+        if let (true, Some(name)) = (path.is_file(), parse_name(config, &path)) {
+            files.push((name, path));
+        }
+    }
+    files.sort_by(|(a, a_path), (b, b_path)| (a.date, a_path).cmp(&(b.date, b_path)));
+    Ok(files)
+}
+
+/// Log files of stand matching template, sorted by date from file name
+pub fn log_files(config: &Config) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    Ok(dated_log_files(config)?.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Log file with latest date in file name, log file of today if there is none
+pub fn latest_log(config: &Config) -> Result<PathBuf, Box<dyn Error>> {
+    match log_files(config)?.pop() {
+        Some(path) => Ok(path),
+        None => config.get_log_file_path(Local::now()),
+    }
+}
+
+/// Latest log file dated before log file `path`, interval closed in `path` can start there
+pub fn previous_log(config: &Config, path: &Path) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let date = match parse_name(config, path) {
+        Some(name) => name.date,
+        None => return Ok(None),
+    };
+    Ok(dated_log_files(config)?
+        .into_iter()
+        .rev()
+        .find(|(name, other)| name.date <= date && other != path)
+        .map(|(_, other)| other))
+}
+
+fn last_written_path(config: &Config) -> PathBuf {
+    config.settings_dir.join(&config.teststand_dir).join(LAST_WRITTEN_FILE)
+}
+
+/// Log file with last written record, new record is linked to it.
+/// It is older than latest log after split of interval from previous day.
+pub fn last_written_log(config: &Config) -> Result<PathBuf, Box<dyn Error>> {
+    if let Ok(name) = fs::read_to_string(last_written_path(config)) {
+        let path = config.get_log_dir_path().join(name.trim());
+        if path.is_file() {
+            return Ok(path);
+        }
+    }
+    latest_log(config)
+}
+
+/// Remember log file with last written record in stand folder
+pub fn set_last_written(config: &Config, path: &Path) -> Result<(), Box<dyn Error>> {
+    let name = path.file_name().ok_or("Log file without name")?.to_string_lossy().to_string();
+    fs::write(last_written_path(config), name)?;
+    Ok(())
+}
+
+/// Exclusive right to write log files of stand. Server, stand process, interlock watcher
+/// and TUI can append records at the same time, ID and hash link are read from log file.
+/// Lock file is removed on drop.
+pub struct LogLock {
+    path: PathBuf,
+}
+
+impl LogLock {
+    /// Wait for lock of log files of stand, stale lock of crashed process is removed
+    pub fn acquire(config: &Config) -> Result<LogLock, Box<dyn Error>> {
+        let path = config.settings_dir.join(&config.teststand_dir).join(LOCK_FILE);
+        let start = Instant::now();
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    write!(file, "{}", std::process::id())?;
+                    return Ok(LogLock { path });
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&path).and_then(|m| m.modified()).ok().and_then(|t| t.elapsed().ok());
+                    if age.is_some_and(|age| age > STALE_LOCK) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(From::from(format!("Log files are locked by other process: {}", path.display())));
+                    }
+                    thread::sleep(time::Duration::from_millis(20));
+                },
+                Err(e) => return Err(From::from(e)),
+            }
+        }
+    }
+}
+
+impl Drop for LogLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Name and content of archived and current log files of stand, sorted by date from file name.
+/// Name of archived log is `<archive>/<file>`.
+pub fn read_logs(config: &Config) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut logs = Vec::new();
+    let archive_dir = config.get_log_dir_path().join(ARCHIVE_DIR);
+    if archive_dir.is_dir() {
+        for entry in fs::read_dir(&archive_dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "zip") {
+                continue;
+            }
+            let mut archive = ZipArchive::new(File::open(&path)?)?;
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                let date = match parse_name(config, Path::new(file.name())) {
+                    Some(name) => name.date,
+                    None => continue,
+                };
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+                logs.push((date, format!("{}/{}", path.display(), file.name()), contents));
+            }
+        }
+    }
+    for (name, path) in dated_log_files(config)? {
+        logs.push((name.date, path.display().to_string(), crate::read_text_file(&path)?));
+    }
+    // Stable sort keeps archived log before current log of same date
+    logs.sort_by_key(|(date, _, _)| *date);
+    Ok(logs.into_iter().map(|(_, name, contents)| (name, contents)).collect())
+}
+
+/// Add file to zip archive, written file is checked by reading it back
+fn append_to_archive(archive: &Path, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut writer = if archive.exists() {
+        let mut reader = ZipArchive::new(File::open(archive)?)?;
+        if let Ok(mut file) = reader.by_name(name) {
+            // Log was archived, but not removed
+            let mut archived = Vec::new();
+            file.read_to_end(&mut archived)?;
+            if archived == contents {
+                return Ok(());
+            }
+            return Err(From::from(format!("Other {} is in archive already: {}", name, archive.display())));
+        }
+        ZipWriter::new_append(fs::OpenOptions::new().read(true).write(true).open(archive)?)?
+    } else {
+        ZipWriter::new(File::create(archive)?)
+    };
+    writer.start_file(name, SimpleFileOptions::default().compression_method(CompressionMethod::Deflated))?;
+    writer.write_all(contents)?;
+    writer.finish()?.sync_all()?;
+
+    let mut archived = Vec::new();
+    ZipArchive::new(File::open(archive)?)?.by_name(name)?.read_to_end(&mut archived)?;
+    if archived != contents {
+        return Err(From::from(format!("{} not written to archive: {}", name, archive.display())));
+    }
+    Ok(())
+}
+
+/// Move log files with period ended more than `days` days ago to monthly zip archives
+/// `<bench>_<yyyy>-<mm>.zip`. Latest log file is kept, state of measurement is read from it.
+/// Log file with last written record is kept, next record is linked to it.
+/// Returns number of archived files.
+pub fn archive_logs(config: &Config, days: i64) -> Result<usize, Box<dyn Error>> {
+    let archive_dir = config.get_log_dir_path().join(ARCHIVE_DIR);
+    let limit = Local::now().date_naive() - Duration::days(days);
+    let mut files = dated_log_files(config)?;
+    files.pop();
+    let last_written = last_written_log(config)?;
+    files.retain(|(_, path)| *path != last_written);
+
+    let mut count = 0;
+    for (name, path) in files {
+        if name.end >= limit {
+            continue;
+        }
+        let bench = match name.bench {
+            Some(bench) => bench,
+            None => config.get_test_bench_id()?,
+        };
+        fs::create_dir_all(&archive_dir)?;
+        let archive = archive_dir.join(format!("{}_{}.zip", bench, name.date.format("%Y-%m")));
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        append_to_archive(&archive, &file_name, &fs::read(&path)?)?;
+        fs::remove_file(&path)?;
+        println!("Archived: {} -> {}", path.display(), archive.display());
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Archive log files older than `days` days, or than `archive_after_days` of madl.cfg
+pub fn archive(config: &Config, days: Option<i64>) -> Result<(), Box<dyn Error>> {
+    let days = match days.or(config.archive_after_days) {
+        Some(days) => days,
+        None => return Err(From::from("Age of archived logs is not set, use --days or archive_after_days in madl.cfg")),
+    };
+    let count = archive_logs(config, days)?;
+    println!("\nArchived {} log files older than {} days to: {}", count, days, config.get_log_dir_path().join(ARCHIVE_DIR).display());
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let daily = LogTemplate::new(DEFAULT_TEMPLATE).unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        assert_eq!(daily.file_name("TB_7", date), "TB_7_191026.txt");
+        assert_eq!(daily.parse("TB_7_191026.txt"), Some(LogName { date, end: date, bench: Some("TB_7".to_string()) }));
+        assert_eq!(daily.parse("TB7_311326.txt"), None);
+
+        let weekly = LogTemplate::new("{bench}_{yyyy}-W{ww}.log").unwrap();
+        // Week of 31/12/2024 belongs to year 2025
+        assert_eq!(weekly.file_name("TB7", NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()), "TB7_2025-W01.log");
+        let week = weekly.parse("TB7_2025-W01.log").unwrap();
+        assert_eq!((week.date, week.end), (NaiveDate::from_ymd_opt(2024, 12, 30).unwrap(), NaiveDate::from_ymd_opt(2025, 1, 5).unwrap()));
+
+        let monthly = LogTemplate::new("{yyyy}-{mm}.log").unwrap();
+        assert_eq!(monthly.period, Period::Month);
+        assert_eq!(monthly.parse("2024-02.log").unwrap().end, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+
+        assert!(LogTemplate::new("{bench}_{dd}{mm}.txt").is_err());
+        assert!(LogTemplate::new("{bench}_{yyyy}-{mm}-{ww}.txt").is_err());
+        assert!(LogTemplate::new("{bench}_{date}.txt").is_err());
+        assert!(LogTemplate::new("logs/{bench}_{yyyy}.txt").is_err());
+    }
+
+    #[test]
+    fn test_archive_logs() {
+        let mut config = crate::tests::temp_stand("archive");
+        let dir = config.settings_dir.clone();
+        config.log_file_template = "{bench}_{yyyy}-{mm}-{dd}.log".to_string();
+        let log_dir = config.get_log_dir_path();
+        fs::create_dir_all(&log_dir).unwrap();
+        // Older log of default template is found after change of template
+        fs::write(log_dir.join("TB1_300926.txt"), "MADL_Version::3.1\r\nTR_Number::TR1\r\n").unwrap();
+        fs::write(log_dir.join("TB1_2026-10-01.log"), "MADL_Version::3.1\r\nTR_Number::TR2\r\n").unwrap();
+        fs::write(log_dir.join("TB1_2026-10-02.log"), "MADL_Version::3.1\r\nTR_Number::TR3\r\n").unwrap();
+        fs::write(log_dir.join("notes.txt"), "not a log").unwrap();
+        assert_eq!(latest_log(&config).unwrap(), log_dir.join("TB1_2026-10-02.log"));
+
+        assert_eq!(archive_logs(&config, 0).unwrap(), 2);
+        assert!(log_dir.join(ARCHIVE_DIR).join("TB1_2026-09.zip").exists());
+        assert!(log_dir.join(ARCHIVE_DIR).join("TB1_2026-10.zip").exists());
+        assert_eq!(log_files(&config).unwrap(), vec!(log_dir.join("TB1_2026-10-02.log")));
+        let logs = read_logs(&config).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let contents: Vec<&str> = logs.iter().map(|(_, contents)| contents.as_str()).collect();
+        assert_eq!(contents, vec!(
+            "MADL_Version::3.1\r\nTR_Number::TR1\r\n",
+            "MADL_Version::3.1\r\nTR_Number::TR2\r\n",
+            "MADL_Version::3.1\r\nTR_Number::TR3\r\n"));
+    }
+}
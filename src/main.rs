@@ -1,154 +1,115 @@
-use madl::{Config, user_inputs, update_output, Laststate, check_state, write_test_definition,
-    DefFile, TcState, read_tc_log, create_config_files, write_missing_test_end,
-    end_of_test, write_test_start, write_test_loss_end, testloose_inputs, write_test_loss};
-use std::process;
-use std::collections::HashMap;
-use madl::Cli;
-use structopt::StructOpt;
-use std::io::prelude::*;
-use std::io;
-use std::sync::mpsc;
-use hotwatch::{Hotwatch, Event};
-
-fn init_output<'a>() -> HashMap<&'a str, String> {
-    // last_line - is last line from log to check last status
-    let mut output: HashMap<&'a str, String> = HashMap::new();
-    output.entry("InterlockStatus").or_default();
-    output.entry("TR_Number").or_default();
-    output.entry("Specimen ID").or_default();
-    output.entry("Test Request type").or_default();
-    output.entry("Testing_Category").or_default();
-    output.entry("Technician").or_default();
-    output.entry("Available Time").or_default();
-    output.entry("last_line").or_default();
-
-    output
-}
-
-fn start_test_definition<'a>(config: &Config, output: &'a HashMap<&'a str, String>) -> HashMap<&'a str, String> {
-    let output = output.to_owned();
-    let output = update_output(&config, &output).unwrap();
-    let deffile = DefFile::new(&config);
-    let output =  deffile.read_temp_output(output).unwrap();
-    let output = match user_inputs(&config, output) {
-        Err(e) => {
-            eprintln!("Application error: {}", e);
-            process::exit(1);
-        },
-        Ok(val) => val,
-    };
-    deffile.write_temp_output(&output).unwrap();
-
-    output
-}
-
-fn start_change_timeloss<'a>(config: &Config, output: &'a HashMap<&'a str, String>) -> HashMap<&'a str, String> {
-    let output = output.to_owned();
-    let output = update_output(&config, &output).unwrap();
-    let last_state = check_state(&output);
-
-    match last_state {
-        Laststate::IN(vec_data) => {
-            if vec_data[0].contains("Test Start") {
-                end_of_test(&config, false).unwrap();
-            } else {
-                write_test_loss_end(&config, &vec_data).unwrap();
-            }
-            let out = testloose_inputs(&config).unwrap();
-            write_test_loss(&config, out).unwrap();
-        },
-        Laststate::OUT(_) => {
-            let out = testloose_inputs(&config).unwrap();
-            write_test_loss(&config, out).unwrap();
-        },
-        Laststate::EMPTY => {
-            let out = testloose_inputs(&config).unwrap();
-            write_test_loss(&config, out).unwrap();
-        },
-    }
-    output
-}
-
-// Get definition of test
-fn test_start_measurement<'a>(config: &Config, output: &'a HashMap<&'a str, String>) -> HashMap<&'a str, String> {
-    let output = output.to_owned();
-    let deffile = DefFile::new(&config);
-    let output = deffile.read_temp_output(output).unwrap();
-    let (tx, rx) = mpsc::channel();
-    let tcroot_folder = config.get_tc_log_folder_path();
-    // watch_folder(tcroot_folder, tx);
-
-    //println!("Checking log folder");
-    let mut hotwatch = Hotwatch::new().expect("hotwatch failed to initialize!");
-    hotwatch.watch(tcroot_folder, move |event: Event| {
-        if let Event::Write(path) = event {
-            //println!("Log file: {:?} changed!", path.display());
-            tx.send(read_tc_log(path)).unwrap();
-        }
-    }).expect("failed to watch file!");
-
-    for received in rx {
-        let output = update_output(&config, &output).unwrap();
-        let last_state = check_state(&output);
-
-        match received {
-            TcState::Start(_) => {
-                match last_state {
-                    Laststate::IN(ref vec_data) => {
-                        if vec_data[0].contains("Test Start") {
-                            println!("\n!!Last log data are from start of test!!\n");
-                            write_missing_test_end(&config).unwrap();
-                            write_test_definition(&config, &output).unwrap();
-                            write_test_start(&config).unwrap();
-                        } else {
-                            write_test_loss_end(&config, &vec_data).unwrap();
-                            write_test_definition(&config, &output).unwrap();
-                            write_test_start(&config).unwrap();
-                        }
-                    }
-                    Laststate::OUT(_) => {
-                        write_test_definition(&config, &output).unwrap();
-                        write_test_start(&config).unwrap();
-                    },
-                    Laststate::EMPTY => {
-                        write_test_definition(&config, &output).unwrap();
-                        write_test_start(&config).unwrap();
-                    },
-                };
-                println!("Measurment started!\n");
-                deffile.remove_temp_file().unwrap();
-            },
-            TcState::End(_) => {
-                match last_state {
-                    Laststate::IN(ref vec_data) => {
-                        if vec_data[0].contains("Test Start") {
-                            if end_of_test(&config, false).unwrap() {
-                                println!("Continue in testing");
-                                continue
-                            };
-                        } else {
-                            write_test_loss_end(&config, vec_data).unwrap();
-                        }
-                    }
-                    Laststate::OUT(ref vec_data) => {
-                        println!("\nLast activity is already stoped: {}->{}->{}\n", vec_data[0], vec_data[1], vec_data[2]);
-                        continue
-                    },
-                    Laststate::EMPTY => panic!("\n!!No record from previous measurement. Start testing again!!\n"),
-                }
-                println!("Measurment end!\n");
-                break
-            },
-            TcState::Empty => {
-                println!("Empty line in TC log");
-                break
-            },
-        };
-    }
-
-    output
-}
-
+use madl::{Config, read_tc_log, create_config_files, track_interlock, interlock, test_progress,
+    overrun_warning, Overrun, resume_test, select_language, lang};
+use madl::workflow::{self, Console};
+use std::process;
+use madl::{Cli, Command, parse_date_arg, print_report, split_test_loss, migrate_logs};
+use structopt::StructOpt;
+use std::io::prelude::*;
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+use hotwatch::{Hotwatch, Event};
+
+/// Period of checking running test against planned duration
+const OVERRUN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+fn start_test_definition(config: &Config) {
+    if let Err(e) = workflow::define_test(config, &mut Console) {
+        eprintln!("Application error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn start_change_timeloss(config: &Config) {
+    if let Err(e) = workflow::change_timeloss(config, &mut Console) {
+        eprintln!("Application error: {}", e);
+    }
+}
+
+fn start_pause(config: &Config) {
+    match workflow::pause_test(config, &mut Console) {
+        Ok(true) => println!("{}\n", lang::text("test_paused")),
+        Ok(false) => println!("\n{}\n", lang::text("no_test_running")),
+        Err(e) => eprintln!("Application error: {}", e),
+    }
+}
+
+fn start_resume(config: &Config) {
+    match resume_test(config) {
+        Ok(true) => println!("{}\n", lang::text("test_resumed")),
+        Ok(false) => println!("\n{}\n", lang::text("test_not_paused")),
+        Err(e) => eprintln!("Application error: {}", e),
+    }
+}
+
+// Get definition of test
+fn test_start_measurement(config: &Config) {
+    let (tx, rx) = mpsc::channel();
+    let tcroot_folder = config.get_tc_log_folder_path();
+    // watch_folder(tcroot_folder, tx);
+    let interlock_path = interlock::watch_path(config);
+    // Watch of same path replaces handler, interlock in TC log is read by handler of TC log
+    let tc_interlock = interlock_path.as_ref() == Some(&tcroot_folder);
+
+    //println!("Checking log folder");
+    let mut hotwatch = Hotwatch::new().expect("hotwatch failed to initialize!");
+    let tc_config = config.clone();
+    hotwatch.watch(tcroot_folder, move |event: Event| {
+        if let Event::Write(path) = event {
+            //println!("Log file: {:?} changed!", path.display());
+            if tc_interlock {
+                if let Err(e) = track_interlock(&tc_config) {
+                    eprintln!("Interlock state not read: {}", e);
+                }
+            }
+            tx.send(read_tc_log(path)).unwrap();
+        }
+    }).expect("failed to watch file!");
+
+    // Interlock changes are logged also during measurement
+    if let Some(interlock_path) = interlock_path.filter(|_| !tc_interlock) {
+        let interlock_config = config.clone();
+        hotwatch.watch(interlock_path, move |event: Event| {
+            match event {
+                Event::Create(_) | Event::Write(_) | Event::Remove(_) | Event::Rename(_, _) => {
+                    if let Err(e) = track_interlock(&interlock_config) {
+                        eprintln!("Interlock state not read: {}", e);
+                    }
+                },
+                _ => (),
+            }
+        }).expect("failed to watch interlock!");
+    }
+
+    let mut warned = Overrun::None;
+    loop {
+        let received = match rx.recv_timeout(OVERRUN_CHECK_INTERVAL) {
+            Ok(received) => received,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                match test_progress(config) {
+                    Ok(Some(progress)) => {
+                        if let Some(text) = overrun_warning(config, &progress, &mut warned) {
+                            println!("\n!!{}!!\n", text);
+                        }
+                    },
+                    Ok(None) => warned = Overrun::None,
+                    Err(e) => eprintln!("Test progress not read: {}", e),
+                }
+                continue
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        match workflow::handle_tc(config, &mut Console, received) {
+            Ok(true) => continue,
+            Ok(false) => break,
+            Err(e) => {
+                eprintln!("Application error: {}", e);
+                break
+            },
+        }
+    }
+}
+
 fn main() {
     let cli = Cli::from_args();
 
@@ -157,19 +118,64 @@ fn main() {
         1..=4 => stand_nm,
         _ => panic!("We have only 4 stands!"),
     };
-    println!("\nRunning measurement on test stand nm: {}\n", stand_nm);
 
     let config = Config::new(stand_nm).unwrap();
     create_config_files(&config);
-    let output = init_output();
-    //println!("{:?}", output);
+    select_language(&config);
+    println!("\n{}\n", lang::format("running_on_stand", &[&stand_nm]));
+    if let Err(e) = madl::repair_torn_logs(&config) {
+        eprintln!("Log files not checked: {}", e);
+    }
+    if let Some(days) = config.archive_after_days {
+        if let Err(e) = madl::logfile::archive_logs(&config, days) {
+            eprintln!("Log files not archived: {}", e);
+        }
+    }
+    #[cfg(feature = "mqtt")]
+    if let Err(e) = madl::mqtt::connect(&config) {
+        eprintln!("MQTT not connected: {}", e);
+    }
+
+    if let Some(cmd) = cli.cmd {
+        let res = match cmd {
+            Command::Report { date } => parse_date_arg(&date).and_then(|date| print_report(&config, date)),
+            Command::Split { date } => parse_date_arg(&date).and_then(|date| split_test_loss(&config, date)),
+            Command::MigrateLogs => migrate_logs(&config),
+            Command::Verify => madl::verify_logs(&config),
+            Command::ArchiveLogs { days } => madl::logfile::archive(&config, days),
+            Command::Pending { clear } => madl::show_pending(&config, clear),
+            Command::RemoveSpecimen { specimen, reason } => madl::remove_specimen(&config, &specimen, &reason),
+            #[cfg(feature = "sqlite")]
+            Command::ImportLogs => madl::storage::import_logs(&config),
+            #[cfg(feature = "tui")]
+            Command::Tui => madl::tui::run(&config),
+            #[cfg(feature = "web")]
+            Command::Serve { bind, port, token } => madl::web::serve(&config, &bind, port, token.as_deref()),
+        };
+        #[cfg(feature = "mqtt")]
+        madl::mqtt::disconnect();
+        if let Err(e) = res {
+            eprintln!("Application error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
 
     loop {
-        println!("\nWelcome in MADL choose from options below:");
-        println!("d - Define test.");
-        println!("c - Change time loss classification.");
-        println!("s - Start test duration measurment");
-        println!("e - Exit\n");
+        if let Err(e) = track_interlock(&config) {
+            eprintln!("Interlock state not read: {}", e);
+        }
+        if let Err(e) = madl::share::flush(&config) {
+            eprintln!("Spooled log not flushed to share: {}", e);
+        }
+        select_language(&config);
+        println!("\n{}", lang::text("menu_welcome"));
+        println!("{}", lang::text("menu_define"));
+        println!("{}", lang::text("menu_change"));
+        println!("{}", lang::text("menu_start"));
+        println!("{}", lang::text("menu_pause"));
+        println!("{}", lang::text("menu_resume"));
+        println!("{}\n", lang::text("menu_exit"));
         print!(">>");
         let mut str_input = String::new();
         io::stdout().flush().unwrap();
@@ -179,16 +185,20 @@ fn main() {
         let answer = answer.trim().to_lowercase().clone();
         //println!("Answer: {}", &answer);
         match  answer.as_ref() {
-            "change" | "c" => start_change_timeloss(&config, &output),
-            "define" | "d" => start_test_definition(&config, &output),
-            "start"  | "s" => test_start_measurement(&config, &output),
+            "change" | "c" => start_change_timeloss(&config),
+            "define" | "d" => start_test_definition(&config),
+            "start"  | "s" => test_start_measurement(&config),
+            "pause"  | "p" => start_pause(&config),
+            "resume" | "r" => start_resume(&config),
             "exit"  | "e" => break,
             _ => {
-                println!("Inserted wrong value, please insert again!");
+                println!("{}", lang::text("wrong_input"));
                 continue;
             },
         };
     }
+    #[cfg(feature = "mqtt")]
+    madl::mqtt::disconnect();
 }
 
 
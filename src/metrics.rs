@@ -0,0 +1,227 @@
+use std::error::Error;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::{Config, logfile, read_history, report};
+use crate::record::LogTime;
+use crate::report::IntervalKind;
+
+/// Content type of OpenMetrics text format, readable by Prometheus
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Metrics of one stand from its Utilization Log folder
+#[derive(Debug, Default)]
+pub struct StandMetrics {
+    pub stand: u8,
+    pub bench: String,
+    /// IN, OUT, PAUSE or EMPTY from last record
+    pub state: String,
+    /// Start of running interval
+    pub interval_start: Option<LogTime>,
+    pub tr_number: String,
+    pub specimen: String,
+    pub tests_started: u64,
+    pub tests_ended: u64,
+    /// Seconds of closed time loss intervals per classification
+    pub loss_seconds: BTreeMap<String, i64>,
+}
+
+/// Counters from log history of stand
+#[derive(Debug, Clone, Default)]
+struct History {
+    interval_start: Option<LogTime>,
+    tests_started: u64,
+    tests_ended: u64,
+    loss_seconds: BTreeMap<String, i64>,
+}
+
+/// Path, size and modification time of log file or archive
+type FileStamp = (PathBuf, u64, SystemTime);
+
+/// History of every log folder with stamps of files it is parsed from.
+/// Log history is parsed again only after change of its files, not on every scrape.
+static CACHE: Mutex<BTreeMap<PathBuf, (Vec<FileStamp>, History)>> = Mutex::new(BTreeMap::new());
+
+/// Stamps of current log files and archives of stand
+fn file_stamps(config: &Config) -> Result<Vec<FileStamp>, Box<dyn Error>> {
+    let mut paths = logfile::log_files(config)?;
+    let archive_dir = config.get_log_dir_path().join(logfile::ARCHIVE_DIR);
+    if archive_dir.is_dir() {
+        for entry in fs::read_dir(&archive_dir)? {
+            paths.push(entry?.path());
+        }
+    }
+    let mut out = Vec::new();
+    for path in paths {
+        let metadata = fs::metadata(&path)?;
+        out.push((path, metadata.len(), metadata.modified()?));
+    }
+    Ok(out)
+}
+
+/// Count tests and time losses in joined log files, intervals continue over midnight
+fn parse_history(config: &Config) -> Result<History, Box<dyn Error>> {
+    let mut out = History::default();
+    let intervals = report::apply_splits(&report::parse_log(&read_history(config)?)?);
+    for interval in intervals.iter() {
+        match (&interval.kind, interval.end) {
+            (IntervalKind::Test, end) => {
+                out.tests_started += 1;
+                if end.is_some() {
+                    out.tests_ended += 1;
+                }
+            },
+            (IntervalKind::Loss(_), Some(end)) => {
+                *out.loss_seconds.entry(interval.label()).or_insert(0) += (end - interval.start).num_seconds();
+            },
+            (IntervalKind::Loss(_), None) => (),
+        }
+    }
+    out.interval_start = intervals.last().filter(|i| i.end.is_none()).map(|i| i.start);
+    Ok(out)
+}
+
+/// History of stand from cache if its log files did not change
+fn history(config: &Config) -> Result<History, Box<dyn Error>> {
+    let stamps = file_stamps(config)?;
+    let key = config.get_log_dir_path();
+    if let Some((cached, history)) = CACHE.lock().unwrap().get(&key) {
+        if *cached == stamps {
+            return Ok(history.clone());
+        }
+    }
+    let history = parse_history(config)?;
+    CACHE.lock().unwrap().insert(key, (stamps, history.clone()));
+    Ok(history)
+}
+
+/// Collect metrics of stand from archived and current log files in its log folder
+pub fn collect(config: &Config) -> Result<StandMetrics, Box<dyn Error>> {
+    let status = report::stand_status(config)?;
+    let history = history(config)?;
+    Ok(StandMetrics {
+        stand: status.stand,
+        bench: status.bench,
+        state: status.state,
+        interval_start: history.interval_start,
+        tr_number: status.tr_number,
+        specimen: status.specimen,
+        tests_started: history.tests_started,
+        tests_ended: history.tests_ended,
+        loss_seconds: history.loss_seconds,
+    })
+}
+
+/// Escape label value, backslash, quote and line feed have to be escaped
+fn label_value(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Metrics of stands in OpenMetrics text format
+pub fn render(stands: &[StandMetrics]) -> String {
+    let mut out = String::new();
+    let labels = |s: &StandMetrics| format!("bench=\"{}\",stand=\"{}\"", label_value(&s.bench), s.stand);
+
+    out.push_str("# TYPE madl_stand_state gauge\n# HELP madl_stand_state State of stand from last record.\n");
+    for s in stands {
+        for state in ["IN", "OUT", "PAUSE", "EMPTY"].iter() {
+            out.push_str(&format!("madl_stand_state{{{},state=\"{}\"}} {}\n", labels(s), state, (s.state == *state) as u8));
+        }
+    }
+    out.push_str("# TYPE madl_stand_interval_start_seconds gauge\n\
+# HELP madl_stand_interval_start_seconds Start of running interval as unix timestamp.\n");
+    for s in stands {
+        if let Some(start) = s.interval_start {
+            out.push_str(&format!("madl_stand_interval_start_seconds{{{}}} {}\n", labels(s), start.timestamp()));
+        }
+    }
+    out.push_str("# TYPE madl_stand_test info\n# HELP madl_stand_test Test request of last test definition.\n");
+    for s in stands {
+        out.push_str(&format!("madl_stand_test_info{{{},tr_number=\"{}\",specimen=\"{}\"}} 1\n",
+            labels(s), label_value(&s.tr_number), label_value(&s.specimen)));
+    }
+    out.push_str("# TYPE madl_tests_started counter\n# HELP madl_tests_started Started tests.\n");
+    for s in stands {
+        out.push_str(&format!("madl_tests_started_total{{{}}} {}\n", labels(s), s.tests_started));
+    }
+    out.push_str("# TYPE madl_tests_ended counter\n# HELP madl_tests_ended Ended tests.\n");
+    for s in stands {
+        out.push_str(&format!("madl_tests_ended_total{{{}}} {}\n", labels(s), s.tests_ended));
+    }
+    out.push_str("# TYPE madl_loss_seconds counter\n# HELP madl_loss_seconds Time of closed time loss intervals.\n# UNIT madl_loss_seconds seconds\n");
+    for s in stands {
+        for (class, seconds) in s.loss_seconds.iter() {
+            out.push_str(&format!("madl_loss_seconds_total{{{},classification=\"{}\"}} {}\n", labels(s), label_value(class), seconds));
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Metrics of all stands, stand with unreadable log is skipped
+pub fn metrics(config: &Config) -> Result<String, Box<dyn Error>> {
+    let mut stands = Vec::new();
+    for stand in config.all_stands()? {
+        match collect(&stand) {
+            Ok(metrics) => stands.push(metrics),
+            Err(e) => eprintln!("Metrics of stand {} not collected: {}", stand.stand_nm, e),
+        }
+    }
+    Ok(render(&stands))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn test_render() {
+        let mut loss_seconds = BTreeMap::new();
+        loss_seconds.insert("Idle Time / No \"sample\"".to_string(), 600);
+        let stand = StandMetrics {
+            stand: 1,
+            bench: "TB1".to_string(),
+            state: "IN".to_string(),
+            tr_number: "TR1".to_string(),
+            tests_started: 2,
+            tests_ended: 1,
+            loss_seconds,
+            ..Default::default()
+        };
+        let text = render(&[stand]);
+        assert!(text.contains("madl_stand_state{bench=\"TB1\",stand=\"1\",state=\"IN\"} 1\n"));
+        assert!(text.contains("madl_stand_state{bench=\"TB1\",stand=\"1\",state=\"OUT\"} 0\n"));
+        assert!(text.contains("madl_tests_started_total{bench=\"TB1\",stand=\"1\"} 2\n"));
+        assert!(text.contains("classification=\"Idle Time / No \\\"sample\\\"\"} 600\n"));
+        assert!(!text.contains("madl_stand_interval_start_seconds{"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_collect_over_midnight() {
+        let config = crate::tests::temp_stand("metrics");
+        let day = |d| Local.with_ymd_and_hms(2020, 2, d, 12, 0, 0).unwrap();
+        fs::write(config.get_log_file_path(day(1)).unwrap(), "MADL_Version::3.1\r\n\
+            IN::2020-02-01T22:00:00+01:00::Test Start::ID=1\r\n\
+            OUT::2020-02-01T23:00:00+01:00::Test Stopped::Finished::none::ID=2\r\n\
+            IN::2020-02-01T23:00:00+01:00::Idle Time::No test sample::Sample Shortage::ID=3\r\n").unwrap();
+        let day2 = config.get_log_file_path(day(2)).unwrap();
+        fs::write(&day2, "MADL_Version::3.1\r\nOUT::2020-02-02T01:00:00+01:00::Idle Time::ID=1\r\n").unwrap();
+        let first = collect(&config).unwrap();
+        // Changed log file is parsed again
+        fs::write(&day2, "MADL_Version::3.1\r\nOUT::2020-02-02T01:00:00+01:00::Idle Time::ID=1\r\n\
+            IN::2020-02-02T01:00:00+01:00::Test Start::ID=2\r\n").unwrap();
+        let second = collect(&config).unwrap();
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+
+        assert_eq!(first.loss_seconds.get("Idle Time / No test sample / Sample Shortage"), Some(&7200));
+        assert_eq!((first.tests_started, first.tests_ended, first.interval_start), (1, 1, None));
+        assert_eq!((second.tests_started, second.tests_ended), (2, 1));
+        assert!(second.interval_start.is_some());
+    }
+}
@@ -0,0 +1,301 @@
+use chrono::SecondsFormat;
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "mqtt")]
+use std::error::Error;
+#[cfg(feature = "mqtt")]
+use std::sync::{Mutex, OnceLock};
+#[cfg(feature = "mqtt")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "mqtt")]
+use std::thread;
+#[cfg(feature = "mqtt")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "mqtt")]
+use rumqttc::{Client, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+
+use crate::record::{Record, RecordKind};
+
+/// MQTT broker for publishing state changes, topics can contain `{bench}` and `{stand}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "MqttConfig::default_port")]
+    pub port: u16,
+    /// Topic of retained current state
+    #[serde(default = "MqttConfig::default_topic")]
+    pub topic: String,
+    /// Topic of retained `online`/`offline` status, `offline` is last will
+    #[serde(default = "MqttConfig::default_status_topic")]
+    pub status_topic: String,
+    /// Topic of operator alerts, e.g. test overrunning planned duration
+    #[serde(default = "MqttConfig::default_alert_topic")]
+    pub alert_topic: String,
+}
+
+impl MqttConfig {
+    fn default_port() -> u16 {
+        1883
+    }
+
+    fn default_topic() -> String {
+        "lab/{bench}/state".to_string()
+    }
+
+    fn default_status_topic() -> String {
+        "lab/{bench}/status".to_string()
+    }
+
+    fn default_alert_topic() -> String {
+        "lab/{bench}/alert".to_string()
+    }
+
+    pub fn topic_for(topic: &str, bench: &str, stand: u8) -> String {
+        topic.replace("{bench}", bench).replace("{stand}", &stand.to_string())
+    }
+}
+
+/// Event published for IN/OUT record
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StateEvent {
+    pub bench: String,
+    pub stand: u8,
+    pub host: String,
+    /// IN, OUT, PAUSE or RESUME
+    pub state: String,
+    pub time: String,
+    pub fields: Vec<String>,
+    pub id: Option<u64>,
+    pub duration: Option<i64>,
+}
+
+/// State event from written record, only IN/OUT/PAUSE/RESUME records with origin change state
+pub fn state_event(record: &Record) -> Option<StateEvent> {
+    let state = match record.kind {
+        RecordKind::In => "IN",
+        RecordKind::Out => "OUT",
+        RecordKind::Pause => "PAUSE",
+        RecordKind::Resume => "RESUME",
+        RecordKind::Split | RecordKind::Interlock | RecordKind::Specimen => return None,
+    };
+    let origin = record.tags.origin.as_ref()?;
+    Some(StateEvent {
+        bench: origin.bench.to_owned(),
+        stand: origin.stand,
+        host: origin.host.to_owned(),
+        state: state.to_string(),
+        time: record.time.to_rfc3339_opts(SecondsFormat::Secs, false),
+        fields: record.fields.clone(),
+        id: record.tags.id,
+        duration: record.tags.duration,
+    })
+}
+
+#[cfg(feature = "mqtt")]
+struct Publisher {
+    client: Client,
+    /// Topic of state events with `{bench}` and `{stand}` from origin of record
+    topic: String,
+    status_topic: String,
+    alert_topic: String,
+    stopping: AtomicBool,
+    connection: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+#[cfg(feature = "mqtt")]
+static PUBLISHER: OnceLock<Publisher> = OnceLock::new();
+
+/// Connect to configured broker, connection is kept in background thread until `disconnect`
+#[cfg(feature = "mqtt")]
+pub fn connect(config: &crate::Config) -> Result<(), Box<dyn Error>> {
+    let mqtt = match &config.mqtt {
+        Some(mqtt) => mqtt,
+        None => return Ok(()),
+    };
+    let bench = config.get_test_bench_id()?;
+    let status_topic = MqttConfig::topic_for(&mqtt.status_topic, &bench, config.stand_nm);
+    let alert_topic = MqttConfig::topic_for(&mqtt.alert_topic, &bench, config.stand_nm);
+
+    let mut options = MqttOptions::new(format!("madl-{}-{}", bench, config.stand_nm), mqtt.host.as_str(), mqtt.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(status_topic.as_str(), "offline", QoS::AtLeastOnce, true));
+    let (client, mut connection) = Client::new(options, 10);
+
+    let publisher = PUBLISHER.get_or_init(|| Publisher {
+        client,
+        topic: mqtt.topic.to_owned(),
+        status_topic,
+        alert_topic,
+        stopping: AtomicBool::new(false),
+        connection: Mutex::new(None),
+    });
+    let handle = thread::spawn(move || {
+        let mut connected = false;
+        for notification in connection.iter() {
+            match notification {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    connected = true;
+                    // Status is published again after every reconnect
+                    if let Err(e) = publisher.client.try_publish(publisher.status_topic.as_str(), QoS::AtLeastOnce, true, "online") {
+                        eprintln!("MQTT status not published: {}", e);
+                    }
+                },
+                Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                Ok(_) => (),
+                Err(e) => {
+                    if publisher.stopping.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if connected {
+                        eprintln!("MQTT connection lost: {}", e);
+                    }
+                    connected = false;
+                    thread::sleep(Duration::from_secs(5));
+                },
+            }
+        }
+    });
+    *publisher.connection.lock().unwrap() = Some(handle);
+    Ok(())
+}
+
+/// Publish retained state event of written record to topic of its origin, records of other stands
+/// are written e.g. by API server. Record is not published without connection.
+#[cfg(feature = "mqtt")]
+pub fn publish_record(record: &Record) -> Result<(), Box<dyn Error>> {
+    let (publisher, event) = match (PUBLISHER.get(), state_event(record)) {
+        (Some(publisher), Some(event)) => (publisher, event),
+        _ => return Ok(()),
+    };
+    let topic = MqttConfig::topic_for(&publisher.topic, &event.bench, event.stand);
+    publisher.client.try_publish(topic, QoS::AtLeastOnce, true, serde_json::to_vec(&event)?)?;
+    Ok(())
+}
+
+/// Publish alert text for operator, alert is not published without connection
+#[cfg(feature = "mqtt")]
+pub fn publish_alert(text: &str) -> Result<(), Box<dyn Error>> {
+    if let Some(publisher) = PUBLISHER.get() {
+        publisher.client.try_publish(publisher.alert_topic.as_str(), QoS::AtLeastOnce, false, text)?;
+    }
+    Ok(())
+}
+
+/// Publish `offline` status and close connection, waits shortly for sending
+#[cfg(feature = "mqtt")]
+pub fn disconnect() {
+    let publisher = match PUBLISHER.get() {
+        Some(publisher) => publisher,
+        None => return,
+    };
+    publisher.stopping.store(true, Ordering::SeqCst);
+    let _ = publisher.client.try_publish(publisher.status_topic.as_str(), QoS::AtLeastOnce, true, "offline");
+    let _ = publisher.client.try_disconnect();
+    let handle = match publisher.connection.lock().unwrap().take() {
+        Some(handle) => handle,
+        None => return,
+    };
+    let start = Instant::now();
+    while !handle.is_finished() && start.elapsed() < Duration::from_secs(2) {
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+    use crate::Origin;
+    use crate::record::Tags;
+    #[cfg(feature = "mqtt")]
+    use std::{fs, io::{Read, Write}, net::TcpListener, sync::mpsc};
+
+    #[test]
+    fn test_state_event() {
+        let origin = Origin { bench: "TB1".to_string(), stand: 2, host: "PC1".to_string() };
+        let mut record = Record {
+            kind: RecordKind::Out,
+            time: FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2020, 2, 1, 10, 0, 0).unwrap(),
+            fields: vec!("Test Stopped".to_string()),
+            tags: Tags { id: Some(3), duration: Some(60), origin: Some(origin), ..Default::default() },
+        };
+        let event = state_event(&record).unwrap();
+        assert_eq!(event.state, "OUT");
+        assert_eq!(event.time, "2020-02-01T10:00:00+01:00");
+        assert_eq!(MqttConfig::topic_for("lab/{bench}/{stand}/state", &event.bench, event.stand), "lab/TB1/2/state");
+
+        record.kind = RecordKind::Split;
+        assert!(state_event(&record).is_none());
+    }
+
+    /// Minimal broker, CONNECT is acknowledged and topics of PUBLISH packets are sent to channel
+    #[cfg(feature = "mqtt")]
+    fn fake_broker(listener: TcpListener, topics: mpsc::Sender<String>) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut byte = [0u8; 1];
+        while stream.read_exact(&mut byte).is_ok() {
+            let kind = byte[0] >> 4;
+            let (mut length, mut shift) = (0, 0);
+            loop {
+                if stream.read_exact(&mut byte).is_err() {
+                    return;
+                }
+                length |= ((byte[0] & 0x7f) as usize) << shift;
+                shift += 7;
+                if byte[0] & 0x80 == 0 {
+                    break;
+                }
+            }
+            let mut body = vec![0u8; length];
+            if stream.read_exact(&mut body).is_err() {
+                return;
+            }
+            match kind {
+                1 => stream.write_all(&[0x20, 2, 0, 0]).unwrap(),
+                3 => {
+                    let end = 2 + u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let _ = topics.send(String::from_utf8_lossy(&body[2..end]).to_string());
+                },
+                _ => (),
+            }
+        }
+    }
+
+    #[cfg(feature = "mqtt")]
+    #[test]
+    fn test_publish_topic_of_origin() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, topics) = mpsc::channel();
+        thread::spawn(move || fake_broker(listener, sender));
+
+        let mut config = crate::tests::temp_stand("mqtt");
+        config.mqtt = Some(MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            topic: "lab/{bench}/{stand}/state".to_string(),
+            status_topic: MqttConfig::default_status_topic(),
+            alert_topic: MqttConfig::default_alert_topic(),
+        });
+        connect(&config).unwrap();
+        // Record of other stand written by API server
+        let record = Record {
+            kind: RecordKind::In,
+            time: FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2020, 2, 1, 10, 0, 0).unwrap(),
+            fields: vec!("Test Start".to_string()),
+            tags: Tags { origin: Some(Origin { bench: "TB2".to_string(), stand: 3, host: "PC1".to_string() }), ..Default::default() },
+        };
+        publish_record(&record).unwrap();
+        let mut received = Vec::new();
+        while let Ok(topic) = topics.recv_timeout(Duration::from_secs(5)) {
+            received.push(topic);
+            if received.iter().any(|t| t == "lab/TB2/3/state") && received.iter().any(|t| t == "lab/TB1/status") {
+                break;
+            }
+        }
+        disconnect();
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+        assert!(received.contains(&"lab/TB1/status".to_string()));
+        assert!(received.contains(&"lab/TB2/3/state".to_string()));
+    }
+}
@@ -0,0 +1,863 @@
+use std::fmt;
+use std::error::Error;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use chrono::{DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDateTime, Offset, SecondsFormat, TimeZone};
+use sha2::{Digest, Sha256};
+
+use crate::{LOG_TIME_FORMAT, Origin};
+
+/// Version of log format written by this build
+pub const MADL_VERSION: Version = Version::V3_1;
+
+/// First version with record ids and hash chain, its records are kept unchanged by migration
+pub const CHAIN_VERSION: Version = Version::V3_0;
+
+/// Version of log format from `MADL_Version` line
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Version {
+    /// Records without id and duration
+    V2_5,
+    /// Records with `ID=` and `DURATION=` tags
+    V3_0,
+    /// Record times in RFC 3339 with UTC offset
+    V3_1,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Result<Version, Box<dyn Error>> {
+        match text.trim() {
+            "2.5" => Ok(Version::V2_5),
+            "3.0" => Ok(Version::V3_0),
+            "3.1" => Ok(Version::V3_1),
+            other => Err(From::from(format!("Unsupported MADL_Version: {}", other))),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Version::V2_5 => write!(f, "2.5"),
+            Version::V3_0 => write!(f, "3.0"),
+            Version::V3_1 => write!(f, "3.1"),
+        }
+    }
+}
+
+/// Time of record with UTC offset, durations between records don't depend on DST changes
+pub type LogTime = DateTime<FixedOffset>;
+
+/// Current local time of record
+pub fn now() -> LogTime {
+    Local::now().fixed_offset()
+}
+
+/// Format time of record for log file of given version, local time without offset before 3.1
+pub fn format_time(time: &LogTime, version: Version) -> String {
+    if version >= Version::V3_1 {
+        time.to_rfc3339_opts(SecondsFormat::Secs, false)
+    } else {
+        time.format(LOG_TIME_FORMAT).to_string()
+    }
+}
+
+/// Local time without offset from older logs in time zone `tz`. Ambiguous time of DST end
+/// is the earlier one unless it is before `previous` record, time in gap of DST start is moved by the gap.
+pub fn resolve_local<Tz: TimeZone>(time: NaiveDateTime, tz: &Tz, previous: Option<LogTime>) -> LogTime {
+    match tz.from_local_datetime(&time) {
+        LocalResult::Single(time) => time.fixed_offset(),
+        LocalResult::Ambiguous(first, second) => {
+            let first = first.fixed_offset();
+            if previous.is_some_and(|previous| first < previous) {
+                second.fixed_offset()
+            } else {
+                first
+            }
+        },
+        LocalResult::None => {
+            // Offset from day before is offset before the gap
+            let offset = tz.offset_from_utc_datetime(&(time - Duration::days(1))).fix();
+            let utc = time - Duration::seconds(offset.local_minus_utc() as i64);
+            tz.from_utc_datetime(&utc).fixed_offset()
+        },
+    }
+}
+
+/// Parse time of record, RFC 3339 or local time of older logs in time zone `tz`, see `resolve_local`
+pub fn parse_time_in<Tz: TimeZone>(text: &str, tz: &Tz, previous: Option<LogTime>) -> Result<LogTime, Box<dyn Error>> {
+    let text = text.trim();
+    match DateTime::parse_from_rfc3339(text) {
+        Ok(time) => Ok(time),
+        Err(_) => Ok(resolve_local(NaiveDateTime::parse_from_str(text, LOG_TIME_FORMAT)?, tz, previous)),
+    }
+}
+
+/// Parse time of record, local time of older logs is in local time zone
+pub fn parse_time(text: &str) -> Result<LogTime, Box<dyn Error>> {
+    parse_time_in(text, &Local, None)
+}
+
+/// Optional tags at the end of record
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags {
+    /// Sequence number of record in log file
+    pub id: Option<u64>,
+    /// Duration of closed interval in seconds, only for OUT records
+    pub duration: Option<i64>,
+    /// Hash of previous written record, see `link_hash`
+    pub prev: Option<String>,
+    pub origin: Option<Origin>,
+    /// Free text comment of operator, written escaped by `escape_text`
+    pub comment: Option<String>,
+}
+
+/// Escape text with backslash. Backslash, CR, LF and tab are always escaped,
+/// whitespace at start or end of text is written as `\u{hex}` so it survives trimming of lines,
+/// other characters are escaped if `special(chars, index)` is true.
+pub(crate) fn escape_chars(text: &str, special: fn(&[char], usize) -> bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_whitespace() && (i == 0 || i + 1 == chars.len()) => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c if special(&chars, i) => {
+                out.push('\\');
+                out.push(c);
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape text for field of log line or value of definition line.
+/// Besides `escape_chars` rules `=` is escaped, so field is not read as tag,
+/// and colon is escaped if it is next to other colon or at start or end of text.
+pub fn escape_text(text: &str) -> String {
+    escape_chars(text, is_field_special)
+}
+
+fn is_field_special(chars: &[char], i: usize) -> bool {
+    match chars[i] {
+        '=' => true,
+        ':' => i == 0 || i + 1 == chars.len() || chars[i - 1] == ':' || chars[i + 1] == ':',
+        _ => false,
+    }
+}
+
+/// Escape part of `ORIGIN` tag like `escape_text`, `;` separating the parts is escaped too
+pub fn escape_origin(text: &str) -> String {
+    escape_chars(text, |chars, i| chars[i] == ';' || is_field_special(chars, i))
+}
+
+/// Reverse of `escape_text`, `escape_origin` and `escape_config`. Unknown escape is kept as written,
+/// so backslashes in logs written before escaping of fields are read unchanged.
+pub fn unescape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => {
+                out.push('\\');
+                break;
+            },
+        };
+        rest = &rest[c.len_utf8()..];
+        match c {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '\\' | ':' | '=' | ',' | ';' => out.push(c),
+            'u' => match rest.strip_prefix('{').and_then(|r| r.split_once('}'))
+                .and_then(|(hex, after)| Some((char::from_u32(u32::from_str_radix(hex, 16).ok()?)?, after))) {
+                Some((c, after)) => {
+                    out.push(c);
+                    rest = after;
+                },
+                _ => out.push_str("\\u"),
+            },
+            c => {
+                out.push('\\');
+                out.push(c);
+            },
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Split text by separator which is not part of escape sequence
+pub fn split_escaped<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    let bytes = text.as_bytes();
+    while i < text.len() {
+        if bytes[i] == b'\\' {
+            // Escaped character is skipped, it can be multibyte
+            i += 1 + text[i + 1..].chars().next().map_or(0, char::len_utf8);
+        } else if text[i..].starts_with(separator) {
+            out.push(&text[start..i]);
+            i += separator.len();
+            start = i;
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    out.push(&text[start..]);
+    out
+}
+
+/// Join fields escaped by `escape_text` with `::`, e.g. for storage of record fields
+pub fn join_fields(fields: &[String]) -> String {
+    fields.iter().map(|f| escape_text(f)).collect::<Vec<_>>().join("::")
+}
+
+/// Reverse of `join_fields`
+pub fn split_joined(text: &str) -> Vec<String> {
+    split_escaped(text, "::").into_iter().map(unescape_text).collect()
+}
+
+/// Split record line to data fields and tags from the end of line
+pub fn split_fields(line: &str) -> (Vec<&str>, Tags) {
+    let mut fields: Vec<&str> = split_escaped(line.trim(), "::");
+    let mut tags = Tags::default();
+
+    while fields.len() > 1 {
+        let field = fields[fields.len() - 1];
+        if let Some(comment) = field.strip_prefix("COMMENT=") {
+            tags.comment = Some(unescape_text(comment));
+        } else if let Some(origin) = Origin::parse(field) {
+            tags.origin = Some(origin);
+        } else if let Some(id) = field.strip_prefix("ID=").and_then(|v| v.parse().ok()) {
+            tags.id = Some(id);
+        } else if let Some(duration) = field.strip_prefix("DURATION=").and_then(|v| v.parse().ok()) {
+            tags.duration = Some(duration);
+        } else if let Some(prev) = field.strip_prefix("PREV=") {
+            tags.prev = Some(prev.to_string());
+        } else {
+            break;
+        }
+        fields.pop();
+    }
+    (fields, tags)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordKind {
+    In,
+    Out,
+    /// Correction record splitting time loss interval
+    Split,
+    /// Change of interlock state or override of disabled interlock
+    Interlock,
+    /// Removal of specimen from running test, fields: `Removed`, specimen id, reason
+    Specimen,
+    /// Interruption of running test, fields: `Test Paused`, optional time loss classification
+    Pause,
+    /// End of test interruption, fields: `Test Resumed`
+    Resume,
+}
+
+/// One IN/OUT/SPLIT record from log file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub kind: RecordKind,
+    pub time: LogTime,
+    /// Fields after timestamp
+    pub fields: Vec<String>,
+    pub tags: Tags,
+}
+
+impl RecordKind {
+    /// Name of record kind at start of log line
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordKind::In => "IN",
+            RecordKind::Out => "OUT",
+            RecordKind::Split => "SPLIT",
+            RecordKind::Interlock => "INTERLOCK",
+            RecordKind::Specimen => "SPECIMEN",
+            RecordKind::Pause => "PAUSE",
+            RecordKind::Resume => "RESUME",
+        }
+    }
+}
+
+impl Record {
+    /// Format record for log file of given version, without line ending
+    pub fn to_line(&self, version: Version) -> String {
+        let mut out = format!("{}::{}", self.kind.name(), format_time(&self.time, version));
+        for field in self.fields.iter() {
+            out.push_str("::");
+            out.push_str(&escape_text(field));
+        }
+        if let Some(comment) = &self.tags.comment {
+            out.push_str(&format!("::COMMENT={}", escape_text(comment)));
+        }
+        if version >= Version::V3_0 {
+            if let Some(id) = self.tags.id {
+                out.push_str(&format!("::ID={}", id));
+            }
+            if let Some(duration) = self.tags.duration {
+                out.push_str(&format!("::DURATION={}", duration));
+            }
+        }
+        if let Some(prev) = &self.tags.prev {
+            out.push_str(&format!("::PREV={}", prev));
+        }
+        if let Some(origin) = &self.tags.origin {
+            out.push_str(&format!("::{}", origin));
+        }
+        out
+    }
+}
+
+/// Line of log file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Version(Version),
+    /// `Key::Value` line of test definition block
+    Definition(String, String),
+    Record(Record),
+    Unknown(String),
+}
+
+pub fn parse_kind(text: &str) -> Option<RecordKind> {
+    match text {
+        "IN" => Some(RecordKind::In),
+        "OUT" => Some(RecordKind::Out),
+        "SPLIT" => Some(RecordKind::Split),
+        "INTERLOCK" => Some(RecordKind::Interlock),
+        "SPECIMEN" => Some(RecordKind::Specimen),
+        "PAUSE" => Some(RecordKind::Pause),
+        "RESUME" => Some(RecordKind::Resume),
+        _ => None,
+    }
+}
+
+fn parse_record(kind: RecordKind, fields: &[&str], tags: Tags, time: LogTime) -> Result<Record, Box<dyn Error>> {
+    Ok(Record { kind, time, fields: fields[2..].iter().map(|s| unescape_text(s)).collect(), tags })
+}
+
+/// Reader of 2.5 records, id and duration are not part of this version
+fn parse_v2_5(kind: RecordKind, fields: &[&str], mut tags: Tags, time: LogTime) -> Result<Record, Box<dyn Error>> {
+    tags.id = None;
+    tags.duration = None;
+    parse_record(kind, fields, tags, time)
+}
+
+/// Reader of 3.0 and 3.1 records, every record has id
+fn parse_v3_0(kind: RecordKind, fields: &[&str], tags: Tags, time: LogTime) -> Result<Record, Box<dyn Error>> {
+    if tags.id.is_none() {
+        return Err(From::from(format!("Record without ID in MADL_Version 3.0: {}", fields.join("::"))));
+    }
+    parse_record(kind, fields, tags, time)
+}
+
+/// Malformed record lines reported already, log files are read again on every refresh
+static REPORTED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Warning about malformed record line, every line is reported once
+fn report_malformed(line: &str, error: &dyn Error) {
+    if REPORTED.lock().unwrap().insert(line.to_string()) {
+        eprintln!("Malformed log record ignored: {}: {}", line, error);
+    }
+}
+
+/// Parse log file content. Records are read by `MADL_Version` of their section,
+/// records before first version line are from version 2.5.
+/// Malformed record, e.g. with invalid time, is `Line::Unknown` with warning.
+pub fn parse_lines(contents: &str) -> Result<Vec<Line>, Box<dyn Error>> {
+    parse_lines_in(contents, &Local)
+}
+
+/// Parse log file content with local times of older records in time zone `tz`
+pub fn parse_lines_in<Tz: TimeZone>(contents: &str, tz: &Tz) -> Result<Vec<Line>, Box<dyn Error>> {
+    let mut version = Version::V2_5;
+    let mut previous = None;
+    let mut out = Vec::new();
+
+    for line in contents.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (fields, tags) = split_fields(line);
+        if fields[0] == "MADL_Version" && fields.len() == 2 {
+            version = Version::parse(fields[1])?;
+            out.push(Line::Version(version));
+            continue;
+        }
+        match parse_kind(fields[0]) {
+            Some(kind) if fields.len() > 2 => {
+                let record = parse_time_in(fields[1], tz, previous).and_then(|time| match version {
+                    Version::V2_5 => parse_v2_5(kind, &fields, tags, time),
+                    Version::V3_0 | Version::V3_1 => parse_v3_0(kind, &fields, tags, time),
+                });
+                match record {
+                    Ok(record) => {
+                        previous = Some(record.time);
+                        out.push(Line::Record(record));
+                    },
+                    Err(e) => {
+                        report_malformed(line.trim(), e.as_ref());
+                        out.push(Line::Unknown(line.trim().to_string()));
+                    },
+                }
+            },
+            _ if fields.len() == 2 => out.push(Line::Definition(unescape_text(fields[0]), unescape_text(fields[1]))),
+            _ => out.push(Line::Unknown(line.trim().to_string())),
+        }
+    }
+    Ok(out)
+}
+
+/// Check if line of log file is record
+pub fn is_record_line(line: &str) -> bool {
+    let (fields, _) = split_fields(line);
+    parse_kind(fields[0]).is_some() && fields.len() > 2
+}
+
+/// Last record line of log file content
+pub fn last_record_line(contents: &str) -> Option<&str> {
+    contents.lines().rev().find(|l| is_record_line(l))
+}
+
+/// Not empty lines after last record of log file content, e.g. definition block
+pub fn lines_since_record(contents: &str) -> Vec<&str> {
+    let mut out: Vec<&str> = contents.lines().rev()
+        .take_while(|l| !is_record_line(l))
+        .filter(|l| !l.trim().is_empty())
+        .collect();
+    out.reverse();
+    out
+}
+
+/// Link of hash chain, SHA-256 of previous written record line and lines written
+/// between it and new record. Line endings are not part of hash.
+pub fn link_hash(previous_record: Option<&str>, lines_before: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for line in previous_record.iter().chain(lines_before.iter()) {
+        hasher.update(line.trim_end().as_bytes());
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Check hash chain of records in log files `(name, content)` ordered by date.
+/// Records are checked in order of writing: records of one log file in order of lines, correction
+/// records can be written to older file later. Next record is first unchecked record of some file
+/// linked to last checked record, earliest of them by time if no record is linked.
+/// Chain starts with first record with hash, returns number of checked records or first broken link.
+/// Removal of last records of latest log file is not detected, no record is linked to them.
+pub fn verify_chain(files: &[(String, String)]) -> Result<usize, String> {
+    struct Link<'a> {
+        time: LogTime,
+        name: &'a str,
+        line_nm: usize,
+        line: &'a str,
+        before: Vec<&'a str>,
+    }
+    let mut files_links = Vec::new();
+    for (name, contents) in files.iter() {
+        let mut links = Vec::new();
+        let mut before = Vec::new();
+        let mut previous = None;
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !is_record_line(line) {
+                before.push(line);
+                continue;
+            }
+            let (fields, _) = split_fields(line);
+            let time = parse_time_in(fields[1], &Local, previous)
+                .map_err(|e| format!("{} line {}: {}", name, i + 1, e))?;
+            previous = Some(time);
+            links.push(Link { time, name, line_nm: i + 1, line, before: std::mem::take(&mut before) });
+        }
+        files_links.push(links);
+    }
+    let is_linked = |previous: Option<&str>, link: &Link| {
+        split_fields(link.line).1.prev.is_some_and(|prev| prev == link_hash(previous, &link.before))
+    };
+
+    // Index of first unchecked record of every file
+    let mut next = vec![0; files_links.len()];
+    let mut previous: Option<&str> = None;
+    let mut started = false;
+    let mut count = 0;
+    loop {
+        let unchecked: Vec<usize> = (0..files_links.len()).filter(|&f| next[f] < files_links[f].len()).collect();
+        // Older file is first for same time
+        let earliest = match unchecked.iter().copied().min_by_key(|&f| files_links[f][next[f]].time) {
+            Some(file) => file,
+            None => break,
+        };
+        let file = std::iter::once(earliest)
+            .chain(unchecked.iter().copied())
+            .find(|&f| is_linked(previous, &files_links[f][next[f]]))
+            .unwrap_or(earliest);
+        let link = &files_links[file][next[file]];
+        next[file] += 1;
+        count += 1;
+
+        let (_, tags) = split_fields(link.line);
+        match tags.prev {
+            // Records before start of chain are not protected, migration can change them
+            Some(_) if !started && previous.is_some() => started = true,
+            Some(_) => {
+                started = true;
+                if !is_linked(previous, link) {
+                    return Err(format!("Broken link in {} line {}: {}", link.name, link.line_nm, link.line.trim()));
+                }
+            },
+            None if started => return Err(format!("Record without hash in {} line {}: {}", link.name, link.line_nm, link.line.trim())),
+            None => (),
+        }
+        previous = Some(link.line);
+    }
+    Ok(count)
+}
+
+/// Version of last section in log file content, 2.5 if there is no version line
+pub fn last_version(lines: &[Line]) -> Version {
+    lines.iter().rev()
+        .find_map(|l| match l {
+            Line::Version(version) => Some(*version),
+            _ => None,
+        })
+        .unwrap_or(Version::V2_5)
+}
+
+/// Next free record id in log file
+pub fn next_id(lines: &[Line]) -> u64 {
+    lines.iter()
+        .filter_map(|l| match l {
+            Line::Record(record) => record.tags.id,
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0) + 1
+}
+
+/// Last IN record in log file if it was not closed by OUT record
+pub fn open_in(lines: &[Line]) -> Option<&Record> {
+    let last = lines.iter().rev().find_map(|l| match l {
+        Line::Record(record) if matches!(record.kind, RecordKind::In | RecordKind::Out) => Some(record),
+        _ => None,
+    })?;
+    match last.kind {
+        RecordKind::In => Some(last),
+        _ => None,
+    }
+}
+
+/// PAUSE record of paused test, `None` if test was resumed or interval ended
+pub fn open_pause(lines: &[Line]) -> Option<&Record> {
+    let last = lines.iter().rev().find_map(|l| match l {
+        Line::Record(record) if matches!(record.kind, RecordKind::In | RecordKind::Out | RecordKind::Pause | RecordKind::Resume) => Some(record),
+        _ => None,
+    })?;
+    match last.kind {
+        RecordKind::Pause => Some(last),
+        _ => None,
+    }
+}
+
+/// Check if log file content has any record
+pub fn has_records(lines: &[Line]) -> bool {
+    lines.iter().any(|l| matches!(l, Line::Record(_)))
+}
+
+/// Last interlock state from INTERLOCK records or `InterlockStatus` of definition block
+pub fn last_interlock(lines: &[Line]) -> Option<String> {
+    lines.iter().rev().find_map(|l| match l {
+        Line::Record(record) if record.kind == RecordKind::Interlock && record.fields[0] != "Override" => Some(record.fields[0].to_owned()),
+        Line::Definition(key, value) if key == "InterlockStatus" => Some(value.to_owned()),
+        _ => None,
+    })
+}
+
+/// Check if log file content has records without ids of version before `CHAIN_VERSION`
+pub fn needs_migration(lines: &[Line]) -> bool {
+    let mut version = Version::V2_5;
+    for line in lines {
+        match line {
+            Line::Version(v) => version = *v,
+            Line::Record(_) if version < CHAIN_VERSION => return true,
+            _ => continue,
+        }
+    }
+    version < CHAIN_VERSION
+}
+
+/// Convert records of log file content before `CHAIN_VERSION` to current version. Records get ids,
+/// OUT records duration and times get UTC offset of local time zone. Lines of newer versions are kept,
+/// hashes of records link to them.
+pub fn migrate(contents: &str) -> Result<String, Box<dyn Error>> {
+    migrate_in(contents, &Local)
+}
+
+/// Convert log file content to current version with local times of older records in time zone `tz`
+pub fn migrate_in<Tz: TimeZone>(contents: &str, tz: &Tz) -> Result<String, Box<dyn Error>> {
+    let lines = parse_lines_in(contents, tz)?;
+    let originals = contents.lines().filter(|l| !l.trim().is_empty());
+    let mut id = next_id(&lines);
+    let mut last_in: Option<LogTime> = None;
+    let mut out = String::new();
+    let mut has_version = false;
+    let mut needs_version = false;
+    let mut version = Version::V2_5;
+
+    for (line, original) in lines.into_iter().zip(originals) {
+        if let Line::Version(v) = line {
+            version = v;
+            has_version = true;
+        }
+        if version >= CHAIN_VERSION {
+            out.push_str(original.trim_end());
+            out.push_str("\r\n");
+            continue;
+        }
+        let text = match line {
+            Line::Version(_) => format!("MADL_Version::{}", MADL_VERSION),
+            Line::Definition(key, value) => format!("{}::{}", escape_text(&key), escape_text(&value)),
+            Line::Unknown(text) => text,
+            Line::Record(mut record) => {
+                needs_version |= !has_version;
+                if record.tags.id.is_none() {
+                    record.tags.id = Some(id);
+                    id += 1;
+                }
+                match record.kind {
+                    RecordKind::In => last_in = Some(record.time),
+                    RecordKind::Out => if let Some(start) = last_in.take() {
+                        record.tags.duration.get_or_insert((record.time - start).num_seconds());
+                    },
+                    RecordKind::Split => for field in record.fields.iter_mut().take(2) {
+                        if let Ok(time) = parse_time_in(field, tz, None) {
+                            *field = format_time(&time, MADL_VERSION);
+                        }
+                    },
+                    RecordKind::Interlock | RecordKind::Specimen | RecordKind::Pause | RecordKind::Resume => (),
+                }
+                record.to_line(MADL_VERSION)
+            },
+        };
+        out.push_str(&text);
+        out.push_str("\r\n");
+    }
+    // Records of file without definition block
+    if needs_version {
+        out.insert_str(0, &format!("MADL_Version::{}\r\n", MADL_VERSION));
+    }
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Prague;
+    use proptest::prelude::*;
+
+    const LOG_2_5: &str = "\
+MADL_Version::2.5\r\n\
+TR_Number::TR1\r\n\
+IN::01/02/2020 08:00:00::Test Start::ORIGIN=TB1;1;PC1\r\n\
+OUT::01/02/2020 10:00:00::Test Stopped::Finished::none\r\n";
+
+    #[test]
+    fn test_parse_versions() {
+        let lines = parse_lines(LOG_2_5).unwrap();
+        assert_eq!(lines[0], Line::Version(Version::V2_5));
+        assert_eq!(lines[1], Line::Definition("TR_Number".to_string(), "TR1".to_string()));
+        assert_eq!(last_version(&lines), Version::V2_5);
+        assert!(needs_migration(&lines));
+
+        let unsupported = "MADL_Version::9.9\r\n";
+        assert!(parse_lines(unsupported).is_err());
+        // Malformed record does not hide other records
+        let malformed = "MADL_Version::3.0\r\nIN::01/02/2020 08:00:00::Test Start\r\nOUT::31/02/2020 09:00:00::Test Stopped::ID=2\r\n\
+            IN::01/02/2020 10:00:00::Test Start::ID=3\r\n";
+        let lines = parse_lines(malformed).unwrap();
+        assert_eq!(lines[1], Line::Unknown("IN::01/02/2020 08:00:00::Test Start".to_string()));
+        assert_eq!(lines[2], Line::Unknown("OUT::31/02/2020 09:00:00::Test Stopped::ID=2".to_string()));
+        assert!(matches!(&lines[3], Line::Record(record) if record.tags.id == Some(3)));
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let mut day1 = String::from("MADL_Version::3.0\r\nTR_Number::TR1\r\n");
+        let first = format!("IN::01/02/2020 08:00:00::Test Start::ID=1::PREV={}", link_hash(None, &["MADL_Version::3.0", "TR_Number::TR1"]));
+        day1.push_str(&format!("{}\r\n", first));
+        let mut day2 = String::from("MADL_Version::3.0\r\n");
+        let second = format!("OUT::02/02/2020 10:00:00::Test Stopped::Finished::none::ID=1::PREV={}", link_hash(Some(&first), &["MADL_Version::3.0"]));
+        day2.push_str(&format!("{}\r\n", second));
+        let third = format!("IN::02/02/2020 10:00:00::Idle Time::No test sample::Sample Shortage::ID=2::PREV={}", link_hash(Some(&second), &[]));
+        day2.push_str(&format!("{}\r\n", third));
+
+        let files = vec!(("TB1_010220.txt".to_string(), day1.clone()), ("TB1_020220.txt".to_string(), day2.clone()));
+        assert_eq!(verify_chain(&files), Ok(3));
+
+        let tampered = vec!(("TB1_010220.txt".to_string(), day1.replace("TR1", "TR2")), ("TB1_020220.txt".to_string(), day2.clone()));
+        assert!(verify_chain(&tampered).unwrap_err().starts_with("Broken link in TB1_010220.txt line 3"));
+
+        // Split written to older log file in same second as record of newer file
+        let split = format!("SPLIT::02/02/2020 10:00:00::01/02/2020 08:00:00::01/02/2020 09:00:00::A::B::C::ID=2::PREV={}", link_hash(Some(&third), &[]));
+        day1.push_str(&format!("{}\r\n", split));
+        let files = vec!(("TB1_010220.txt".to_string(), day1.clone()), ("TB1_020220.txt".to_string(), day2.clone()));
+        assert_eq!(verify_chain(&files), Ok(4));
+        // Removed split breaks link of next record
+        let fourth = format!("OUT::02/02/2020 11:00:00::Test Stopped::Finished::none::ID=3::PREV={}", link_hash(Some(&split), &[]));
+        day2.push_str(&format!("{}\r\n", fourth));
+        let files = vec!(("TB1_010220.txt".to_string(), day1.replace(&split, "")), ("TB1_020220.txt".to_string(), day2));
+        assert!(verify_chain(&files).unwrap_err().starts_with("Broken link in TB1_020220.txt line 4"));
+    }
+
+    #[test]
+    fn test_comment() {
+        let comment = "cracked at 12k cycles:: see C:\\photos\nnext line";
+        let record = Record {
+            kind: RecordKind::Out,
+            time: DateTime::parse_from_rfc3339("2020-02-01T10:00:00+01:00").unwrap(),
+            fields: vec!("Test Stopped".to_string(), "Finished".to_string(), "none".to_string()),
+            tags: Tags { id: Some(2), comment: Some(comment.to_string()), ..Default::default() },
+        };
+        let line = record.to_line(MADL_VERSION);
+        assert_eq!(line, "OUT::2020-02-01T10:00:00+01:00::Test Stopped::Finished::none::COMMENT=cracked at 12k cycles\\:\\: see C:\\\\photos\\nnext line::ID=2");
+        let (fields, tags) = split_fields(&line);
+        assert_eq!(fields.len(), 5);
+        assert_eq!(tags.comment.as_deref(), Some(comment));
+        assert_eq!(split_escaped("a\\:::b", "::"), vec!("a\\:", "b"));
+    }
+
+    #[test]
+    fn test_escape_fields() {
+        assert_eq!(escape_text("10:00:00"), "10:00:00");
+        assert_eq!(escape_text(":a::b:"), "\\:a\\:\\:b\\:");
+        assert_eq!(escape_text("ID=1"), "ID\\=1");
+        assert_eq!(escape_text(" a\tb "), "\\u{20}a\\tb\\u{20}");
+        // Backslash of older logs without escaping is read unchanged
+        assert_eq!(unescape_text("C:\\photos\\u{zz}\\"), "C:\\photos\\u{zz}\\");
+        let line = "IN::01/02/2020 08:00:00::Test Start::ID\\=1::ID=2";
+        let lines = parse_lines(&format!("MADL_Version::3.0\r\n{}\r\nTR\\:\\:::TR1\\:\\:2\r\n", line)).unwrap();
+        match &lines[1] {
+            Line::Record(record) => assert_eq!((record.fields[1].as_str(), record.tags.id), ("ID=1", Some(2))),
+            other => panic!("Not a record: {:?}", other),
+        }
+        assert_eq!(lines[2], Line::Definition("TR::".to_string(), "TR1::2".to_string()));
+    }
+
+    #[test]
+    fn test_origin_round_trip() {
+        let origin = Origin { bench: "TB;1::A".to_string(), stand: 2, host: "PC=1:".to_string() };
+        let record = Record {
+            kind: RecordKind::In,
+            time: DateTime::parse_from_rfc3339("2020-02-01T08:00:00+01:00").unwrap(),
+            fields: vec!("Test Start".to_string()),
+            tags: Tags { id: Some(1), origin: Some(origin.clone()), ..Default::default() },
+        };
+        let line = record.to_line(MADL_VERSION);
+        assert!(line.ends_with("::ORIGIN=TB\\;1\\:\\:A;2;PC\\=1\\:"));
+        let contents = format!("MADL_Version::3.1\r\n{}\r\n", line);
+        assert_eq!(parse_lines(&contents).unwrap()[1], Line::Record(record));
+        // Host with `;` of log written before escaping of origin
+        assert_eq!(Origin::parse("ORIGIN=TB1;1;PC;2").unwrap().host, "PC;2");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_record_round_trip(fields in prop::collection::vec(any::<String>(), 1..5), comment in any::<Option<String>>()) {
+            let record = Record {
+                kind: RecordKind::In,
+                time: DateTime::parse_from_rfc3339("2020-02-01T08:00:00+01:00").unwrap(),
+                fields,
+                tags: Tags { id: Some(1), comment, ..Default::default() },
+            };
+            let contents = format!("MADL_Version::3.1\r\n{}\r\n", record.to_line(MADL_VERSION));
+            prop_assert_eq!(parse_lines(&contents).unwrap(), vec!(Line::Version(Version::V3_1), Line::Record(record)));
+        }
+
+        #[test]
+        fn prop_definition_round_trip(key in "[A-Za-z_][A-Za-z_ ]*[A-Za-z_]", value in any::<String>()) {
+            let contents = migrate(&format!("MADL_Version::3.0\r\n{}::{}\r\n", key, escape_text(&value))).unwrap();
+            prop_assert_eq!(&parse_lines(&contents).unwrap()[1], &Line::Definition(key, value));
+        }
+
+        #[test]
+        fn prop_joined_fields(fields in prop::collection::vec(any::<String>(), 1..5)) {
+            prop_assert_eq!(split_joined(&join_fields(&fields)), fields);
+        }
+    }
+
+    #[test]
+    fn test_migrate() {
+        let migrated = migrate_in(LOG_2_5, &Prague).unwrap();
+        assert_eq!(migrated, "\
+MADL_Version::3.1\r\n\
+TR_Number::TR1\r\n\
+IN::2020-02-01T08:00:00+01:00::Test Start::ID=1::ORIGIN=TB1;1;PC1\r\n\
+OUT::2020-02-01T10:00:00+01:00::Test Stopped::Finished::none::ID=2::DURATION=7200\r\n");
+
+        let lines = parse_lines(&migrated).unwrap();
+        assert!(!needs_migration(&lines));
+        assert_eq!(next_id(&lines), 3);
+        assert_eq!(migrate(&migrated).unwrap(), migrated);
+    }
+
+    #[test]
+    fn test_migrate_chain() {
+        // Stand was updated to version with hash chain during the day
+        let first = "IN::01/02/2020 08:00:00::Test Start";
+        let mut day1 = format!("MADL_Version::2.5\r\nTR_Number::TR1\r\n{}\r\nMADL_Version::3.0\r\n", first);
+        let second = format!("OUT::01/02/2020 10:00:00::Test Stopped::Finished::none::ID=1::PREV={}", link_hash(Some(first), &["MADL_Version::3.0"]));
+        day1.push_str(&format!("{}\r\n", second));
+        let day2 = format!("MADL_Version::3.0\r\nIN::02/02/2020 10:00:00::Idle Time::No test sample::Sample Shortage::ID=2::PREV={}\r\n",
+            link_hash(Some(&second), &["MADL_Version::3.0"]));
+        let files = vec!(("TB1_010220.txt".to_string(), day1.clone()), ("TB1_020220.txt".to_string(), day2.clone()));
+        assert_eq!(verify_chain(&files), Ok(3));
+
+        assert!(!needs_migration(&parse_lines(&day2).unwrap()));
+        assert_eq!(migrate(&day2).unwrap(), day2);
+        let migrated = migrate_in(&day1, &Prague).unwrap();
+        assert!(migrated.starts_with("MADL_Version::3.1\r\nTR_Number::TR1\r\nIN::2020-02-01T08:00:00+01:00::Test Start::ID=2\r\n"));
+        assert!(migrated.ends_with(&format!("MADL_Version::3.0\r\n{}\r\n", second)));
+        let files = vec!(("TB1_010220.txt".to_string(), migrated), ("TB1_020220.txt".to_string(), day2.clone()));
+        assert_eq!(verify_chain(&files), Ok(3));
+    }
+
+    #[test]
+    fn test_dst_times() {
+        // Clock goes back from 03:00 to 02:00 in the night of 25/10/2026
+        let first = parse_time_in("25/10/2026 02:30:00", &Prague, None).unwrap();
+        let second = parse_time_in("25/10/2026 02:10:00", &Prague, Some(first)).unwrap();
+        assert_eq!(format_time(&first, MADL_VERSION), "2026-10-25T02:30:00+02:00");
+        assert_eq!(format_time(&second, MADL_VERSION), "2026-10-25T02:10:00+01:00");
+        assert_eq!(second - first, Duration::minutes(40));
+        // Time skipped at 29/03/2026 02:00 is moved behind the gap
+        let skipped = parse_time_in("29/03/2026 02:30:00", &Prague, None).unwrap();
+        assert_eq!(format_time(&skipped, MADL_VERSION), "2026-03-29T03:30:00+02:00");
+        assert_eq!(format_time(&skipped, Version::V3_0), "29/03/2026 03:30:00");
+        assert_eq!(parse_time("2026-10-25T02:10:00+01:00").unwrap(), second);
+
+        let log = "\
+MADL_Version::2.5\r\n\
+IN::25/10/2026 01:50:00::Test Start\r\n\
+OUT::25/10/2026 02:30:00::Test Stopped::Finished::none\r\n\
+IN::25/10/2026 02:30:00::Idle Time::No test sample::Sample Shortage\r\n\
+OUT::25/10/2026 02:10:00::Idle Time::No test sample::Sample Shortage\r\n";
+        let migrated = migrate_in(log, &Prague).unwrap();
+        assert!(migrated.contains("OUT::2026-10-25T02:10:00+01:00::Idle Time::No test sample::Sample Shortage::ID=4::DURATION=2400"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::io::prelude::*;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::{read_text_file, parse_config};
+
+/// Test request from TR registry config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrEntry {
+    pub tr_number: String,
+    pub customer: String,
+    pub project: String,
+    pub specimens: Vec<String>,
+    /// Planned test duration in hours
+    pub planned_duration: String,
+    pub status: String,
+    pub request_type: String,
+    pub category: String,
+}
+
+impl TrEntry {
+    pub fn is_closed(&self) -> bool {
+        self.status.eq_ignore_ascii_case("closed")
+    }
+
+    pub fn expects_specimen(&self, specimen: &str) -> bool {
+        self.specimens.iter().any(|s| s == specimen)
+    }
+}
+
+/// Multi line config file, one test request per line:
+/// `TR number,customer,project,specimen;specimen,planned hours,status,request type,category`
+pub struct TrRegistry {
+    pub entries: Vec<TrEntry>,
+}
+
+/// Number of single character edits between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl TrRegistry {
+    /// Read registry, registry without file is empty
+    pub fn new(path: &Path) -> Result<TrRegistry, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(TrRegistry { entries: Vec::new() });
+        }
+        let config_str = read_text_file(&path.to_path_buf())?;
+        let mut entries = Vec::new();
+
+        for line in parse_config(&config_str) {
+            if line.len() < 8 {
+                continue;
+            }
+            let line: Vec<String> = line.iter().map(|v| v.trim().to_string()).collect();
+            entries.push(TrEntry {
+                tr_number: line[0].clone(),
+                customer: line[1].clone(),
+                project: line[2].clone(),
+                specimens: line[3].split(';').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                planned_duration: line[4].clone(),
+                status: line[5].clone(),
+                request_type: line[6].clone(),
+                category: line[7].clone(),
+            });
+        }
+        Ok(TrRegistry { entries })
+    }
+
+    pub fn create_empty(path: PathBuf) -> Result<(), Box<dyn Error>> {
+        if !path.exists() {
+            let mut f = fs::OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+            f.write_all(b"TR0001,Customer,Project,S1;S2,16,Open,text,endurance\r\n")?;
+        };
+        Ok(())
+    }
+
+    pub fn find(&self, tr_number: &str) -> Option<&TrEntry> {
+        self.entries.iter().find(|e| e.tr_number.eq_ignore_ascii_case(tr_number.trim()))
+    }
+
+    /// Entries similar to inserted text, best match first
+    pub fn search(&self, text: &str) -> Vec<&TrEntry> {
+        let text = text.trim().to_lowercase();
+        let mut found: Vec<(usize, &TrEntry)> = self.entries.iter()
+            .filter_map(|e| {
+                let tr = e.tr_number.to_lowercase();
+                if tr.contains(&text) || text.contains(&tr) {
+                    return Some((0, e));
+                }
+                let distance = edit_distance(&tr, &text);
+                if distance <= 2 + tr.len() / 4 {
+                    Some((distance, e))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        found.sort_by_key(|(distance, _)| *distance);
+        found.into_iter().map(|(_, e)| e).take(10).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> TrRegistry {
+        let entry = |tr: &str, status: &str| TrEntry {
+            tr_number: tr.to_string(),
+            customer: "Customer".to_string(),
+            project: "Project".to_string(),
+            specimens: vec!("S1".to_string()),
+            planned_duration: "16".to_string(),
+            status: status.to_string(),
+            request_type: "Validation".to_string(),
+            category: "endurance".to_string(),
+        };
+        TrRegistry { entries: vec!(entry("TR1234", "Open"), entry("TR5678", "Closed"), entry("XY0001", "Open")) }
+    }
+
+    #[test]
+    fn test_search() {
+        let registry = registry();
+        assert!(registry.find("tr1234").is_some());
+        assert!(registry.find("TR5678").unwrap().is_closed());
+        let found: Vec<&str> = registry.search("TR1243").iter().map(|e| e.tr_number.as_str()).collect();
+        assert_eq!(found, vec!("TR1234"));
+        assert_eq!(registry.search("5678").len(), 1);
+        assert!(registry.search("ABCDEFGH").is_empty());
+    }
+}
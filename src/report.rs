@@ -0,0 +1,789 @@
+use std::fmt;
+use std::error::Error;
+use std::collections::BTreeMap;
+use chrono::{Duration, Local, NaiveDate};
+use serde::Serialize;
+
+use crate::{Config, Laststate, LOG_TIME_FORMAT, Origin, Specimen, check_state, get_logs_data, log_history, logfile};
+use crate::record::{self, Line, LogTime, Record, RecordKind, Tags};
+
+/// Kind of logged interval
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntervalKind {
+    Test,
+    Loss(Vec<String>),
+}
+
+/// Interruption of test between PAUSE and RESUME record
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pause {
+    pub start: LogTime,
+    pub end: Option<LogTime>,
+    /// Time loss classification, empty if not classified
+    pub reason: Vec<String>,
+}
+
+impl Pause {
+    /// Name used for summary in report
+    pub fn label(&self) -> String {
+        if self.reason.is_empty() {
+            "Test Paused".to_string()
+        } else {
+            self.reason.join(" / ")
+        }
+    }
+}
+
+/// Interval between IN and OUT record in log
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interval {
+    pub start: LogTime,
+    pub end: Option<LogTime>,
+    pub kind: IntervalKind,
+    /// Pauses of test interval
+    pub pauses: Vec<Pause>,
+    /// Comments of IN and OUT record
+    pub comments: Vec<String>,
+}
+
+impl Interval {
+    pub fn is_loss(&self) -> bool {
+        match self.kind {
+            IntervalKind::Loss(_) => true,
+            IntervalKind::Test => false,
+        }
+    }
+
+    /// Duration of interval, open interval is counted until `now`
+    pub fn duration(&self, now: LogTime) -> Duration {
+        self.end.unwrap_or(now) - self.start
+    }
+
+    /// Duration of pause, pause not resumed lasts until end of interval
+    pub fn pause_duration(&self, pause: &Pause, now: LogTime) -> Duration {
+        pause.end.or(self.end).unwrap_or(now) - pause.start
+    }
+
+    /// Duration without pauses
+    pub fn active_duration(&self, now: LogTime) -> Duration {
+        self.pauses.iter().fold(self.duration(now), |total, pause| total - self.pause_duration(pause, now))
+    }
+
+    /// Name used for summary in report
+    pub fn label(&self) -> String {
+        match &self.kind {
+            IntervalKind::Test => "Test".to_string(),
+            IntervalKind::Loss(class) => class.join(" / "),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let end = match self.end {
+            Some(end) => end.format(LOG_TIME_FORMAT).to_string(),
+            None => "running".to_string(),
+        };
+        write!(f, "{} -> {} {}", self.start.format(LOG_TIME_FORMAT), end, self.label())
+    }
+}
+
+/// One part of split time loss interval from SPLIT correction record
+#[derive(Debug, Clone, PartialEq)]
+pub struct Split {
+    pub written: LogTime,
+    pub interval_start: LogTime,
+    pub start: LogTime,
+    pub class: Vec<String>,
+}
+
+/// SPLIT correction record for one part of time loss interval
+pub fn split_record(written: LogTime, interval_start: LogTime, start: LogTime, class: Vec<String>, origin: Origin) -> Record {
+    let mut fields = vec!(
+        record::format_time(&interval_start, record::MADL_VERSION),
+        record::format_time(&start, record::MADL_VERSION));
+    fields.extend(class);
+    Record { kind: RecordKind::Split, time: written, fields, tags: Tags { origin: Some(origin), ..Default::default() } }
+}
+
+fn parse_split(record: &Record) -> Option<Split> {
+    if record.fields.len() < 5 {
+        return None;
+    }
+    Some(Split {
+        written: record.time,
+        interval_start: record::parse_time(&record.fields[0]).ok()?,
+        start: record::parse_time(&record.fields[1]).ok()?,
+        class: record.fields[2..5].to_vec(),
+    })
+}
+
+/// Time of one specimen in test interval, ends with test or removal of specimen
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecimenPeriod {
+    pub specimen: String,
+    pub start: LogTime,
+    pub end: Option<LogTime>,
+    pub removed: bool,
+}
+
+/// Test interval with TR number and planned duration of its definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestRun {
+    pub tr_number: String,
+    pub planned: Option<Duration>,
+    pub start: LogTime,
+    pub end: Option<LogTime>,
+}
+
+/// Parsed content of one log file
+#[derive(Debug, Default)]
+pub struct LogContent {
+    pub intervals: Vec<Interval>,
+    pub splits: Vec<Split>,
+    pub specimens: Vec<SpecimenPeriod>,
+    pub runs: Vec<TestRun>,
+}
+
+/// Planned duration from `Available Time` value in hours
+pub fn planned_duration(available_time: &str) -> Option<Duration> {
+    let hours = available_time.trim().parse::<f64>().ok()?;
+    Some(Duration::seconds((hours * 3600.0) as i64))
+}
+
+/// End pause of test interval
+fn end_pause(interval: &mut Interval, time: LogTime) {
+    if let Some(pause) = interval.pauses.last_mut().filter(|p| p.end.is_none()) {
+        pause.end = Some(time);
+    }
+}
+
+/// Close open test run
+fn end_run(runs: &mut [TestRun], time: LogTime) {
+    if let Some(run) = runs.last_mut().filter(|r| r.end.is_none()) {
+        run.end = Some(time);
+    }
+}
+
+/// Close open periods of specimens, all specimens if `specimen` is `None`
+fn end_specimens(periods: &mut [SpecimenPeriod], time: LogTime, specimen: Option<&str>) {
+    for period in periods.iter_mut().filter(|p| p.end.is_none() && specimen.is_none_or(|s| s == p.specimen)) {
+        period.end = Some(time);
+        period.removed = specimen.is_some();
+    }
+}
+
+/// Parse IN/OUT records and correction records from log file content
+pub fn parse_log(contents: &str) -> Result<LogContent, Box<dyn Error>> {
+    let mut out = LogContent::default();
+    let mut open: Option<Interval> = None;
+    // Removed specimens stay removed until next definition
+    let mut specimens: Vec<Specimen> = Vec::new();
+    let mut removed: Vec<String> = Vec::new();
+    let mut tr_number = String::new();
+    let mut planned = None;
+
+    for line in record::parse_lines(contents)? {
+        let record = match line {
+            Line::Record(record) => record,
+            Line::Definition(key, value) if key == "Specimen ID" => {
+                specimens = Specimen::parse_list(&value);
+                removed.clear();
+                continue;
+            },
+            Line::Definition(key, value) if key == "TR_Number" => {
+                tr_number = value.trim().to_string();
+                continue;
+            },
+            Line::Definition(key, value) if key == "Available Time" => {
+                planned = planned_duration(&value);
+                continue;
+            },
+            _ => continue,
+        };
+        match record.kind {
+            RecordKind::Interlock => continue,
+            RecordKind::Specimen => {
+                if record.fields.len() > 1 && record.fields[0] == "Removed" {
+                    end_specimens(&mut out.specimens, record.time, Some(&record.fields[1]));
+                    removed.push(record.fields[1].to_owned());
+                }
+            },
+            RecordKind::Split => {
+                if let Some(split) = parse_split(&record) {
+                    out.splits.push(split);
+                }
+            },
+            RecordKind::Pause => {
+                // New PAUSE of paused test changes reason of pause
+                if let Some(interval) = open.as_mut().filter(|i| i.kind == IntervalKind::Test) {
+                    end_pause(interval, record.time);
+                    interval.pauses.push(Pause { start: record.time, end: None, reason: record.fields.iter().skip(1).cloned().collect() });
+                    end_specimens(&mut out.specimens, record.time, None);
+                    end_run(&mut out.runs, record.time);
+                }
+            },
+            RecordKind::Resume => {
+                let paused = open.as_ref().and_then(|i| i.pauses.last()).is_some_and(|p| p.end.is_none());
+                if let (true, Some(interval)) = (paused, open.as_mut()) {
+                    end_pause(interval, record.time);
+                    for specimen in specimens.iter().filter(|s| !removed.contains(&s.id)) {
+                        out.specimens.push(SpecimenPeriod { specimen: specimen.id.to_owned(), start: record.time, end: None, removed: false });
+                    }
+                    out.runs.push(TestRun { tr_number: tr_number.to_owned(), planned, start: record.time, end: None });
+                }
+            },
+            RecordKind::In => {
+                // Missing OUT record, previous interval ends with new one
+                if let Some(mut interval) = open.take() {
+                    end_pause(&mut interval, record.time);
+                    interval.end = Some(record.time);
+                    out.intervals.push(interval);
+                }
+                end_specimens(&mut out.specimens, record.time, None);
+                end_run(&mut out.runs, record.time);
+                let comments = record.tags.comment.iter().cloned().collect();
+                let kind = if record.fields[0] == "Test Start" {
+                    out.runs.push(TestRun { tr_number: tr_number.to_owned(), planned, start: record.time, end: None });
+                    for specimen in specimens.iter().filter(|s| !removed.contains(&s.id)) {
+                        out.specimens.push(SpecimenPeriod { specimen: specimen.id.to_owned(), start: record.time, end: None, removed: false });
+                    }
+                    IntervalKind::Test
+                } else {
+                    IntervalKind::Loss(record.fields)
+                };
+                open = Some(Interval { start: record.time, end: None, kind, pauses: Vec::new(), comments });
+            },
+            RecordKind::Out => {
+                if let Some(mut interval) = open.take() {
+                    end_pause(&mut interval, record.time);
+                    interval.end = Some(record.time);
+                    interval.comments.extend(record.tags.comment);
+                    out.intervals.push(interval);
+                }
+                end_specimens(&mut out.specimens, record.time, None);
+                end_run(&mut out.runs, record.time);
+            },
+        }
+    }
+    if let Some(interval) = open {
+        out.intervals.push(interval);
+    }
+    Ok(out)
+}
+
+/// Check that records and definition blocks are from given test bench and stand
+pub fn verify_origin(contents: &str, bench: &str, stand: u8) -> Vec<String> {
+    let mut out = Vec::new();
+
+    for (l, line) in contents.lines().enumerate() {
+        let (fields, tags) = record::split_fields(line);
+        let (bench_found, stand_found) = match (fields[0], tags.origin) {
+            (_, Some(origin)) => (Some(origin.bench), origin.stand.to_string()),
+            ("TestBench ID", _) if fields.len() > 1 => (Some(record::unescape_text(fields[1])), stand.to_string()),
+            ("Stand Number", _) if fields.len() > 1 => (None, record::unescape_text(fields[1])),
+            _ => continue,
+        };
+        if bench_found.as_ref().is_some_and(|b| b != bench) || stand_found != stand.to_string() {
+            out.push(format!("line {} is not from test bench {} stand {}: {}", l + 1, bench, stand, line.trim()));
+        }
+    }
+    out
+}
+
+/// Replace time loss intervals by their split parts. Last written split of interval wins.
+pub fn apply_splits(content: &LogContent) -> Vec<Interval> {
+    let mut out = Vec::new();
+
+    for interval in content.intervals.iter() {
+        let latest = content.splits.iter()
+            .filter(|s| s.interval_start == interval.start)
+            .map(|s| s.written)
+            .max();
+        let written = match latest {
+            Some(written) if interval.is_loss() => written,
+            _ => {
+                out.push(interval.clone());
+                continue;
+            },
+        };
+        let mut parts: Vec<&Split> = content.splits.iter()
+            .filter(|s| s.interval_start == interval.start && s.written == written)
+            .collect();
+        parts.sort_by_key(|s| s.start);
+        for (i, part) in parts.iter().enumerate() {
+            let end = match parts.get(i + 1) {
+                Some(next) => Some(next.start),
+                None => interval.end,
+            };
+            // Comments stay with first part of split interval
+            let comments = if i == 0 { interval.comments.clone() } else { Vec::new() };
+            out.push(Interval { start: part.start, end, kind: IntervalKind::Loss(part.class.clone()), pauses: Vec::new(), comments });
+        }
+    }
+    out
+}
+
+/// Sum of durations per interval label, pauses of test are summed by their label
+pub fn summary(intervals: &[Interval], now: LogTime) -> BTreeMap<String, Duration> {
+    let mut out: BTreeMap<String, Duration> = BTreeMap::new();
+    for interval in intervals {
+        let total = out.entry(interval.label()).or_insert_with(Duration::zero);
+        *total += interval.active_duration(now);
+        for pause in interval.pauses.iter() {
+            *out.entry(pause.label()).or_insert_with(Duration::zero) += interval.pause_duration(pause, now);
+        }
+    }
+    out
+}
+
+/// Test time per specimen, open period is counted until `now`
+pub fn specimen_summary(periods: &[SpecimenPeriod], now: LogTime) -> BTreeMap<String, Duration> {
+    let mut out: BTreeMap<String, Duration> = BTreeMap::new();
+    for period in periods {
+        let total = out.entry(period.specimen.to_owned()).or_insert_with(Duration::zero);
+        *total += period.end.unwrap_or(now) - period.start;
+    }
+    out
+}
+
+/// Planned and actual test time of one TR
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrTime {
+    /// Planned duration from last definition of TR
+    pub planned: Option<Duration>,
+    pub actual: Duration,
+}
+
+impl TrTime {
+    /// Actual minus planned time, positive if test overran plan
+    pub fn delta(&self) -> Option<Duration> {
+        self.planned.map(|planned| self.actual - planned)
+    }
+}
+
+/// Test time per TR, open run is counted until `now`
+pub fn tr_summary(runs: &[TestRun], now: LogTime) -> BTreeMap<String, TrTime> {
+    let mut out: BTreeMap<String, TrTime> = BTreeMap::new();
+    for run in runs.iter().filter(|r| !r.tr_number.is_empty()) {
+        let total = out.entry(run.tr_number.to_owned()).or_insert(TrTime { planned: None, actual: Duration::zero() });
+        total.actual += run.end.unwrap_or(now) - run.start;
+        if run.planned.is_some() {
+            total.planned = run.planned;
+        }
+    }
+    out
+}
+
+/// Specimens removed from running test after last definition block
+pub fn removed_specimens(contents: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut out = Vec::new();
+    for line in record::parse_lines(contents)? {
+        match line {
+            Line::Definition(key, _) if key == "Specimen ID" => out.clear(),
+            Line::Record(record) if record.kind == RecordKind::Specimen && record.fields.len() > 1 && record.fields[0] == "Removed" => {
+                out.push(record.fields[1].to_owned());
+            },
+            _ => (),
+        }
+    }
+    Ok(out)
+}
+
+/// Time from `start` to `end` cut to range `from`..`to`, `None` outside of range.
+/// Open end stays open until `to` is reached by `now`.
+fn cut(start: LogTime, end: Option<LogTime>, from: LogTime, to: LogTime, now: LogTime) -> Option<(LogTime, Option<LogTime>)> {
+    if start >= to || end.unwrap_or(now) <= from {
+        return None;
+    }
+    let end = match end {
+        Some(end) => Some(end.min(to)),
+        None if now >= to => Some(to),
+        None => None,
+    };
+    Some((start.max(from), end))
+}
+
+/// Intervals cut to range `from`..`to`, e.g. day of log file. Pauses are cut with their test.
+pub fn cut_intervals(intervals: &[Interval], from: LogTime, to: LogTime, now: LogTime) -> Vec<Interval> {
+    intervals.iter()
+        .filter_map(|i| {
+            let (start, end) = cut(i.start, i.end, from, to, now)?;
+            let pauses = i.pauses.iter()
+                .filter_map(|p| cut(p.start, p.end.or(i.end), from, to, now).map(|(start, end)| Pause { start, end, reason: p.reason.clone() }))
+                .collect();
+            Some(Interval { start, end, pauses, ..i.clone() })
+        })
+        .collect()
+}
+
+/// Specimen periods cut to range `from`..`to`, e.g. day of log file
+pub fn cut_specimens(periods: &[SpecimenPeriod], from: LogTime, to: LogTime, now: LogTime) -> Vec<SpecimenPeriod> {
+    periods.iter()
+        .filter_map(|p| cut(p.start, p.end, from, to, now).map(|(start, end)| SpecimenPeriod { start, end, ..p.clone() }))
+        .collect()
+}
+
+/// Format duration as HH:MM:SS
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.num_seconds();
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
+/// Format difference of durations as +HH:MM:SS or -HH:MM:SS
+pub fn format_delta(delta: Duration) -> String {
+    let sign = if delta < Duration::zero() { "-" } else { "+" };
+    format!("{}{}", sign, format_duration(delta.abs()))
+}
+
+/// Interval of timeline in stand status
+#[derive(Debug, Serialize)]
+pub struct TimelineEntry {
+    #[serde(skip)]
+    pub start_time: LogTime,
+    pub start: String,
+    pub end: Option<String>,
+    pub kind: String,
+    pub label: String,
+    pub seconds: i64,
+}
+
+/// Utilization of stand from today's intervals
+#[derive(Debug, Default, Serialize)]
+pub struct Kpi {
+    pub test_seconds: i64,
+    pub loss_seconds: i64,
+    /// Test time from all logged time in percent
+    pub utilization: f64,
+    /// Seconds per time loss classification
+    pub loss_by_class: BTreeMap<String, i64>,
+}
+
+/// Current state of one stand with today's timeline
+#[derive(Debug, Serialize)]
+pub struct StandStatus {
+    pub stand: u8,
+    pub bench: String,
+    /// IN, OUT, PAUSE or EMPTY from last record
+    pub state: String,
+    pub detail: Vec<String>,
+    pub tr_number: String,
+    pub specimen: String,
+    pub timeline: Vec<TimelineEntry>,
+    pub kpi: Kpi,
+}
+
+/// Utilization from intervals, pauses of test are time loss
+pub fn kpi(intervals: &[Interval], now: LogTime) -> Kpi {
+    let mut out = Kpi::default();
+    for interval in intervals {
+        let seconds = interval.active_duration(now).num_seconds();
+        if interval.is_loss() {
+            out.loss_seconds += seconds;
+            *out.loss_by_class.entry(interval.label()).or_insert(0) += seconds;
+        } else {
+            out.test_seconds += seconds;
+        }
+        for pause in interval.pauses.iter() {
+            let seconds = interval.pause_duration(pause, now).num_seconds();
+            out.loss_seconds += seconds;
+            *out.loss_by_class.entry(pause.label()).or_insert(0) += seconds;
+        }
+    }
+    let total = out.test_seconds + out.loss_seconds;
+    if total > 0 {
+        out.utilization = 100.0 * out.test_seconds as f64 / total as f64;
+    }
+    out
+}
+
+/// State of stand from last record and today's timeline from its log folder
+pub fn stand_status(config: &Config) -> Result<StandStatus, Box<dyn Error>> {
+    let now = Local::now();
+    // State can be from log file of previous day
+    let last = get_logs_data(&logfile::log_files(config)?, &config.definition_fields)?;
+    let (state, detail) = match check_state(&last) {
+        Laststate::IN(vec_data) => ("IN", vec_data),
+        Laststate::OUT(vec_data) => ("OUT", vec_data),
+        Laststate::PAUSE(vec_data) => ("PAUSE", vec_data),
+        Laststate::EMPTY => ("EMPTY", Vec::new()),
+    };
+
+    // Interval started before midnight is cut to today, log file can hold more days
+    let midnight = |date: NaiveDate| record::resolve_local(date.and_hms_opt(0, 0, 0).unwrap(), &Local, None);
+    let today = now.date_naive();
+    let now = now.fixed_offset();
+    let intervals = cut_intervals(&apply_splits(&log_history(config)?), midnight(today), midnight(today + Duration::days(1)), now);
+    let mut timeline = Vec::new();
+    for i in intervals.iter() {
+        timeline.push(TimelineEntry {
+            start_time: i.start,
+            start: i.start.format(LOG_TIME_FORMAT).to_string(),
+            end: i.end.map(|end| end.format(LOG_TIME_FORMAT).to_string()),
+            kind: if i.is_loss() { "loss".to_string() } else { "test".to_string() },
+            label: i.label(),
+            seconds: i.duration(now).num_seconds(),
+        });
+        // Pauses are drawn over their test interval
+        for pause in i.pauses.iter() {
+            timeline.push(TimelineEntry {
+                start_time: pause.start,
+                start: pause.start.format(LOG_TIME_FORMAT).to_string(),
+                end: pause.end.or(i.end).map(|end| end.format(LOG_TIME_FORMAT).to_string()),
+                kind: "pause".to_string(),
+                label: pause.label(),
+                seconds: i.pause_duration(pause, now).num_seconds(),
+            });
+        }
+    }
+
+    Ok(StandStatus {
+        stand: config.stand_nm,
+        bench: config.get_test_bench_id()?,
+        state: state.to_string(),
+        detail,
+        tr_number: last.definition.tr_number,
+        specimen: last.definition.specimen_id,
+        timeline,
+        kpi: kpi(&intervals, now),
+    })
+}
+
+/// Report of log file with period `from`..`to` from joined log `history`. Interval started
+/// on previous day continues in period, interval not closed until end of past period ends with it.
+/// Planned vs actual time of TRs tested in period is counted from all runs.
+pub fn report(history: &LogContent, from: LogTime, to: LogTime, now: LogTime) -> String {
+    let intervals = cut_intervals(&apply_splits(history), from, to, now);
+    let mut text = String::new();
+
+    text.push_str("Intervals:\n");
+    for interval in intervals.iter() {
+        text.push_str(&format!("  {} ({})\n", interval, format_duration(interval.duration(now))));
+        for pause in interval.pauses.iter() {
+            let end = match pause.end.or(interval.end) {
+                Some(end) => end.format(LOG_TIME_FORMAT).to_string(),
+                None => "paused".to_string(),
+            };
+            text.push_str(&format!("    pause {} -> {} {} ({})\n", pause.start.format(LOG_TIME_FORMAT), end,
+                pause.label(), format_duration(interval.pause_duration(pause, now))));
+        }
+        for comment in interval.comments.iter() {
+            text.push_str(&format!("    comment: {}\n", comment.replace('\n', " ")));
+        }
+    }
+    text.push_str("\nSummary:\n");
+    for (label, duration) in summary(&intervals, now) {
+        text.push_str(&format!("  {}: {}\n", label, format_duration(duration)));
+    }
+    let specimens = cut_specimens(&history.specimens, from, to, now);
+    if !specimens.is_empty() {
+        text.push_str("\nSpecimen test time:\n");
+        for (specimen, duration) in specimen_summary(&specimens, now) {
+            let removed = specimens.iter().rev().find(|p| p.specimen == specimen).is_some_and(|p| p.removed);
+            text.push_str(&format!("  {}: {}{}\n", specimen, format_duration(duration), if removed { " (removed)" } else { "" }));
+        }
+    }
+    let runs: Vec<&TestRun> = history.runs.iter().filter(|r| cut(r.start, r.end, from, to, now).is_some()).collect();
+    if runs.iter().any(|r| !r.tr_number.is_empty()) {
+        text.push_str("\nPlanned vs actual test time:\n");
+        for (tr, time) in tr_summary(&history.runs, now).iter().filter(|(tr, _)| runs.iter().any(|r| &&r.tr_number == tr)) {
+            let planned = match (time.planned, time.delta()) {
+                (Some(planned), Some(delta)) => format!("{} ({})", format_duration(planned), format_delta(delta)),
+                _ => "-".to_string(),
+            };
+            text.push_str(&format!("  {}: {} / planned {}\n", tr, format_duration(time.actual), planned));
+        }
+    }
+    text
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+MADL_Version::2.5\r\n\
+TR_Number::TR1\r\n\
+IN::01/02/2020 08:00:00::Test Start\r\n\
+OUT::01/02/2020 10:00:00::Test Stopped::Finished::none\r\n\
+IN::01/02/2020 10:00:00::Idle Time::No test sample::Sample Shortage\r\n\
+OUT::01/02/2020 12:00:00::Idle Time::No test sample::Sample Shortage\r\n";
+
+    fn time(text: &str) -> LogTime {
+        record::parse_time(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let content = parse_log(LOG).unwrap();
+        assert_eq!(content.intervals.len(), 2);
+        assert_eq!(content.intervals[0].kind, IntervalKind::Test);
+        assert_eq!(content.intervals[1].duration(time("01/02/2020 12:00:00")), Duration::hours(2));
+    }
+
+    #[test]
+    fn test_dst_duration() {
+        // Test runs over the clock change from summer to winter time
+        let log = "\
+MADL_Version::3.1\r\n\
+IN::2026-10-25T01:30:00+02:00::Test Start::ID=1\r\n\
+OUT::2026-10-25T02:10:00+01:00::Test Stopped::Finished::none::ID=2\r\n";
+        let content = parse_log(log).unwrap();
+        assert_eq!(content.intervals[0].duration(time("2026-10-25T03:00:00+01:00")), Duration::minutes(100));
+    }
+
+    #[test]
+    fn test_apply_splits() {
+        let class = vec!("Unplanned DownTime".to_string(), "Breakdown of utilities".to_string(), "Air Cool Fail".to_string());
+        let origin = Origin { bench: "TB1".to_string(), stand: 1, host: "PC1".to_string() };
+        let idle = vec!("Idle Time".to_string(), "No test sample".to_string(), "Sample Shortage".to_string());
+        let mut log = LOG.to_string();
+        log.push_str(&split_record(time("01/02/2020 13:00:00"), time("01/02/2020 10:00:00"), time("01/02/2020 10:00:00"), idle, origin.clone()).to_line(record::MADL_VERSION));
+        log.push_str("\r\n");
+        log.push_str(&split_record(time("01/02/2020 13:00:00"), time("01/02/2020 10:00:00"), time("01/02/2020 10:30:00"), class.clone(), origin).to_line(record::MADL_VERSION));
+        log.push_str("\r\n");
+
+        let intervals = apply_splits(&parse_log(&log).unwrap());
+        assert_eq!(intervals.len(), 3);
+        assert_eq!(intervals[2].kind, IntervalKind::Loss(class));
+        let sum = summary(&intervals, time("01/02/2020 13:00:00"));
+        assert_eq!(sum["Idle Time / No test sample / Sample Shortage"], Duration::minutes(30));
+        assert_eq!(sum["Unplanned DownTime / Breakdown of utilities / Air Cool Fail"], Duration::minutes(90));
+
+        let kpi = kpi(&intervals, time("01/02/2020 13:00:00"));
+        assert_eq!(kpi.test_seconds, 7200);
+        assert_eq!(kpi.loss_seconds, 7200);
+        assert_eq!(kpi.utilization, 50.0);
+    }
+
+    #[test]
+    fn test_specimen_summary() {
+        let log = "\
+Specimen ID::S1@A;S2@B\r\n\
+IN::01/02/2020 08:00:00::Test Start\r\n\
+SPECIMEN::01/02/2020 09:00:00::Removed::S2::Crack\r\n\
+OUT::01/02/2020 10:00:00::Test Stopped::Finished::none\r\n\
+IN::01/02/2020 10:00:00::Test Start\r\n";
+        let content = parse_log(log).unwrap();
+        assert_eq!(content.intervals.len(), 2);
+        let sum = specimen_summary(&content.specimens, time("01/02/2020 11:00:00"));
+        assert_eq!(sum["S1"], Duration::hours(3));
+        assert_eq!(sum["S2"], Duration::hours(1));
+        assert!(content.specimens[1].removed);
+        assert_eq!(removed_specimens(log).unwrap(), vec!("S2"));
+
+        // Test started on previous day, specimen removed on previous day stays removed
+        let day2 = "OUT::02/02/2020 02:00:00::Test Stopped::Finished::none\r\n";
+        let history = parse_log(&format!("{}{}", log, day2)).unwrap();
+        let text = report(&history, time("02/02/2020 00:00:00"), time("03/02/2020 00:00:00"), time("02/02/2020 12:00:00"));
+        assert!(text.contains("Specimen test time:\n  S1: 02:00:00\n"));
+        assert!(!text.contains("S2"));
+        assert_eq!(removed_specimens(&format!("{}{}", log, day2)).unwrap(), vec!("S2"));
+    }
+
+    #[test]
+    fn test_report_past_day() {
+        let day1 = "\
+IN::01/02/2020 08:00:00::Test Start\r\n\
+PAUSE::01/02/2020 20:00:00::Test Paused\r\n\
+RESUME::01/02/2020 21:00:00::Test Resumed\r\n\
+OUT::01/02/2020 22:00:00::Test Stopped::Finished::none\r\n\
+IN::01/02/2020 22:00:00::Idle Time::No test sample::Sample Shortage\r\n";
+        let day2 = "OUT::02/02/2020 01:00:00::Idle Time::No test sample::Sample Shortage\r\n\
+IN::02/02/2020 01:00:00::Unplanned DownTime::Breakdown of utilities::Air Cool Fail\r\n";
+        let history = parse_log(&format!("{}{}", day1, day2)).unwrap();
+        let now = time("04/02/2020 12:00:00");
+
+        // Loss interval of first day is closed by first OUT of next day
+        let intervals = cut_intervals(&history.intervals, time("01/02/2020 00:00:00"), time("02/02/2020 00:00:00"), now);
+        let sum = summary(&intervals, now);
+        assert_eq!(sum["Test"], Duration::hours(13));
+        assert_eq!(sum["Test Paused"], Duration::hours(1));
+        assert_eq!(sum["Idle Time / No test sample / Sample Shortage"], Duration::hours(2));
+        // Interval not closed ends with past day
+        let intervals = cut_intervals(&history.intervals, time("02/02/2020 00:00:00"), time("03/02/2020 00:00:00"), now);
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(intervals[1].end, Some(time("03/02/2020 00:00:00")));
+        assert_eq!(summary(&intervals, now)["Unplanned DownTime / Breakdown of utilities / Air Cool Fail"], Duration::hours(23));
+        // Running interval of today is open
+        let today = cut_intervals(&history.intervals, time("02/02/2020 00:00:00"), time("03/02/2020 00:00:00"), time("02/02/2020 12:00:00"));
+        assert_eq!(today[1].end, None);
+    }
+
+    #[test]
+    fn test_stand_status_open_interval() {
+        let config = crate::tests::temp_stand("status");
+        std::fs::create_dir_all(config.get_log_dir_path()).unwrap();
+        // Test started yesterday evening is still running
+        let yesterday = Local::now() - Duration::days(1);
+        let start = record::resolve_local(yesterday.date_naive().and_hms_opt(20, 0, 0).unwrap(), &Local, None);
+        std::fs::write(config.get_log_file_path(yesterday).unwrap(),
+            format!("MADL_Version::3.1\r\nIN::{}::Test Start::ID=1\r\n", start.to_rfc3339())).unwrap();
+
+        let status = stand_status(&config);
+        std::fs::remove_dir_all(&config.settings_dir).unwrap();
+        let status = status.unwrap();
+        let midnight = record::resolve_local(Local::now().date_naive().and_hms_opt(0, 0, 0).unwrap(), &Local, None);
+        assert_eq!(status.state, "IN");
+        assert_eq!(status.timeline.len(), 1);
+        assert_eq!((status.timeline[0].start_time, status.timeline[0].end.as_ref()), (midnight, None));
+    }
+
+    #[test]
+    fn test_pause() {
+        let log = "\
+TR_Number::TR1\r\n\
+IN::01/02/2020 08:00:00::Test Start\r\n\
+PAUSE::01/02/2020 09:00:00::Test Paused\r\n\
+PAUSE::01/02/2020 09:30:00::Test Paused::Idle Time::No test sample::Sample Shortage\r\n\
+RESUME::01/02/2020 10:00:00::Test Resumed\r\n\
+OUT::01/02/2020 12:00:00::Test Stopped::Finished::none\r\n";
+        let content = parse_log(log).unwrap();
+        assert_eq!(content.intervals.len(), 1);
+        assert_eq!(content.intervals[0].pauses.len(), 2);
+        let now = time("01/02/2020 13:00:00");
+        let sum = summary(&content.intervals, now);
+        assert_eq!(sum["Test"], Duration::hours(3));
+        assert_eq!(sum["Test Paused"], Duration::minutes(30));
+        assert_eq!(sum["Idle Time / No test sample / Sample Shortage"], Duration::minutes(30));
+        assert_eq!(tr_summary(&content.runs, now)["TR1"].actual, Duration::hours(3));
+        assert_eq!(kpi(&content.intervals, now).loss_seconds, 3600);
+    }
+
+    #[test]
+    fn test_tr_summary() {
+        let log = "\
+TR_Number::TR1\r\n\
+Available Time::2\r\n\
+IN::01/02/2020 08:00:00::Test Start\r\n\
+OUT::01/02/2020 09:00:00::Test Stopped::Finished::none\r\n\
+IN::01/02/2020 09:00:00::Idle Time::No test sample::Sample Shortage\r\n\
+IN::01/02/2020 10:00:00::Test Start\r\n\
+TR_Number::TR2\r\n\
+Available Time::0.5\r\n\
+IN::01/02/2020 12:00:00::Test Start\r\n";
+        let content = parse_log(log).unwrap();
+        assert_eq!(content.runs.len(), 3);
+        let sum = tr_summary(&content.runs, time("01/02/2020 12:15:00"));
+        assert_eq!(sum["TR1"].actual, Duration::hours(3));
+        assert_eq!(sum["TR1"].delta(), Some(Duration::hours(1)));
+        assert_eq!(sum["TR2"].delta(), Some(Duration::minutes(-15)));
+        assert_eq!(format_delta(Duration::minutes(-15)), "-00:15:00");
+        let day = (time("01/02/2020 00:00:00"), time("02/02/2020 00:00:00"));
+        assert!(report(&content, day.0, day.1, time("01/02/2020 12:15:00")).contains("TR1: 03:00:00 / planned 02:00:00 (+01:00:00)"));
+    }
+
+    #[test]
+    fn test_verify_origin() {
+        let log = "\
+TestBench ID::TB1\r\n\
+Stand Number::1\r\n\
+IN::01/02/2020 08:00:00::Test Start::ORIGIN=TB1;1;PC1\r\n\
+OUT::01/02/2020 10:00:00::Test Stopped::Finished::none::COMMENT=cracked\\: 12k cycles::ORIGIN=TB2;1;PC2\r\n";
+        assert_eq!(parse_log(log).unwrap().intervals[0].end, Some(time("01/02/2020 10:00:00")));
+        assert_eq!(parse_log(log).unwrap().intervals[0].comments, vec!("cracked: 12k cycles"));
+        assert!(verify_origin(log, "TB1", 1)[0].starts_with("line 4 "));
+        assert_eq!(verify_origin(log, "TB1", 2).len(), 3);
+    }
+}
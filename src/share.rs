@@ -0,0 +1,132 @@
+use std::fs;
+use std::io::prelude::*;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::{Config, record};
+
+fn append(path: &Path, text: &str) -> std::io::Result<()> {
+    let mut f = fs::OpenOptions::new().append(true).create(true).open(path)?;
+    f.write_all(text.as_bytes())?;
+    f.sync_all()
+}
+
+/// Share is available if its root folder exists, e.g. mounted drive
+fn share_log_dir(config: &Config) -> Option<PathBuf> {
+    let dir = config.get_share_log_dir_path()?;
+    if !config.share_dir.as_ref()?.is_dir() {
+        return None;
+    }
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// Text of spool not yet in destination file. Spool is split to units of record line with
+/// lines before it, unit is skipped if its record with id is in destination already.
+/// Lines after last record are skipped if destination ends with them.
+pub fn pending_text(destination: &str, spool: &str) -> String {
+    let written: Vec<&str> = destination.lines().map(|l| l.trim_end()).collect();
+    let mut out = String::new();
+    let mut unit = String::new();
+    let mut unit_lines: Vec<&str> = Vec::new();
+
+    for line in spool.lines() {
+        unit.push_str(line.trim_end());
+        unit.push_str("\r\n");
+        unit_lines.push(line.trim_end());
+        let (fields, tags) = record::split_fields(line);
+        if record::parse_kind(fields[0]).is_none() {
+            continue;
+        }
+        if tags.id.is_none() || !written.contains(&line.trim_end()) {
+            out.push_str(&unit);
+        }
+        unit.clear();
+        unit_lines.clear();
+    }
+    if !unit_lines.is_empty() && !written.ends_with(&unit_lines) {
+        out.push_str(&unit);
+    }
+    out
+}
+
+/// Append spooled text to share in order of writing, spool file is removed when its text is on share.
+/// Returns false if share is not available.
+pub fn flush(config: &Config) -> Result<bool, Box<dyn Error>> {
+    let spool_dir = config.get_spool_dir_path();
+    if config.share_dir.is_none() || !spool_dir.exists() {
+        return Ok(true);
+    }
+    let share_dir = match share_log_dir(config) {
+        Some(dir) => dir,
+        None => return Ok(false),
+    };
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&spool_dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect();
+    paths.sort();
+    for path in paths {
+        let destination = share_dir.join(path.file_name().unwrap());
+        let written = if destination.exists() {
+            match fs::read_to_string(&destination) {
+                Ok(text) => text,
+                Err(_) => return Ok(false),
+            }
+        } else {
+            String::new()
+        };
+        let text = pending_text(&written, &fs::read_to_string(&path)?);
+        if append(&destination, &text).is_err() {
+            return Ok(false);
+        }
+        fs::remove_file(&path)?;
+    }
+    Ok(true)
+}
+
+/// Write text appended to local log file also to share. Text goes to spool
+/// if share is not available or older text is waiting in spool.
+pub fn write(config: &Config, log_path: &Path, text: &str) -> Result<(), Box<dyn Error>> {
+    if config.share_dir.is_none() {
+        return Ok(());
+    }
+    let file_name = match log_path.file_name() {
+        Some(name) => name,
+        None => return Err(From::from(format!("Invalid log path: {}", log_path.display()))),
+    };
+    let sent = flush(config)? && match share_log_dir(config) {
+        Some(dir) => append(&dir.join(file_name), text).is_ok(),
+        None => false,
+    };
+    if !sent {
+        let spool_dir = config.get_spool_dir_path();
+        fs::create_dir_all(&spool_dir)?;
+        append(&spool_dir.join(file_name), text)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_text() {
+        let spool = "\
+MADL_Version::3.0\r\n\
+TR_Number::TR1\r\n\
+IN::01/02/2020 08:00:00::Test Start::ID=1\r\n\
+OUT::01/02/2020 10:00:00::Test Stopped::Finished::none::ID=2::DURATION=7200\r\n\
+TR_Number::TR2\r\n";
+        assert_eq!(pending_text("", spool), spool);
+        // Interrupted flush, first unit is on share already
+        let written = "MADL_Version::3.0\r\nTR_Number::TR1\r\nIN::01/02/2020 08:00:00::Test Start::ID=1\r\n";
+        assert_eq!(pending_text(written, spool), "\
+OUT::01/02/2020 10:00:00::Test Stopped::Finished::none::ID=2::DURATION=7200\r\n\
+TR_Number::TR2\r\n");
+        assert_eq!(pending_text(spool, spool), "");
+    }
+}
@@ -0,0 +1,728 @@
+use std::error::Error;
+#[cfg(feature = "sqlite")]
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "sqlite")]
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::path::PathBuf;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "sqlite")]
+use chrono::NaiveDateTime;
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{Config, Origin, LastLogState, TestDefinition, share, append_file, append_record, format_output, get_logs_data,
+    logfile, read_text_file, record};
+use crate::interlock::Interlock;
+use crate::record::Record;
+#[cfg(feature = "sqlite")]
+use crate::record::LogTime;
+#[cfg(feature = "sqlite")]
+use crate::record::{Line, RecordKind};
+
+/// Storage of log records configured in `madl.cfg`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum StorageBackend {
+    /// Text log files only
+    #[default]
+    Text,
+    /// Text log files and SQLite database, state of stand is read from database
+    Sqlite {
+        database: PathBuf,
+    },
+}
+
+/// Storage of log records and test definitions
+pub trait Storage {
+    /// Append record, returns record with id and duration assigned by storage
+    fn append_record(&mut self, record: Record) -> Result<Record, Box<dyn Error>>;
+
+    /// Write test definition block
+    fn write_definition(&mut self, definition: &TestDefinition, origin: &Origin, interlock: Interlock) -> Result<(), Box<dyn Error>>;
+
+    /// Last test definition and last IN/OUT record
+    fn log_data(&self) -> Result<LastLogState, Box<dyn Error>>;
+}
+
+/// Storages of stand, text log of given day first. Records are written to every storage,
+/// state is read from last one.
+pub fn open(config: &Config, date: DateTime<Local>) -> Result<Vec<Box<dyn Storage + '_>>, Box<dyn Error>> {
+    let text: Box<dyn Storage> = Box::new(TextStorage { config, date });
+    match &config.storage {
+        StorageBackend::Text => Ok(vec!(text)),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite { database } => Ok(vec!(text, Box::new(SqliteStorage::open(database, Origin::new(config)?)?))),
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite { .. } => Err(From::from("Storage Sqlite is configured, but madl is built without sqlite feature")),
+    }
+}
+
+/// Text log files named by log file template in log folder of stand
+pub struct TextStorage<'a> {
+    config: &'a Config,
+    /// Day of log file written to
+    date: DateTime<Local>,
+}
+
+impl TextStorage<'_> {
+    /// Local log is complete without share, error is only reported
+    fn write_share(&self, filename: &std::path::Path, text: &str) {
+        if let Err(e) = share::write(self.config, filename, text) {
+            eprintln!("Log not written to share or spool: {}", e);
+        }
+    }
+}
+
+impl Storage for TextStorage<'_> {
+    fn append_record(&mut self, record: Record) -> Result<Record, Box<dyn Error>> {
+        let filename = self.config.get_log_file_path(self.date)?;
+        let lock = logfile::LogLock::acquire(self.config)?;
+        // Interval can start in log file from previous day
+        let previous = match logfile::previous_log(self.config, &filename)? {
+            Some(path) => Some(read_text_file(&path)?),
+            None => None,
+        };
+        // Last record can be in older log file after split of interval
+        let last_written = match logfile::last_written_log(self.config) {
+            Ok(path) if path.exists() && path != filename => Some(read_text_file(&path)?),
+            _ => None,
+        };
+        let (record, text) = append_record(filename.clone(), record, previous.as_deref(), last_written.as_deref())?;
+        logfile::set_last_written(self.config, &filename)?;
+        drop(lock);
+        self.write_share(&filename, &text);
+        Ok(record)
+    }
+
+    fn write_definition(&mut self, definition: &TestDefinition, origin: &Origin, interlock: Interlock) -> Result<(), Box<dyn Error>> {
+        let filename = self.config.get_log_file_path(self.date)?;
+        let text = format_output(definition, &self.config.definition_fields, origin, interlock);
+        // Definition block is hashed with next record
+        let lock = logfile::LogLock::acquire(self.config)?;
+        append_file(filename.clone(), text.clone())?;
+        drop(lock);
+        self.write_share(&filename, &text);
+        Ok(())
+    }
+
+    fn log_data(&self) -> Result<LastLogState, Box<dyn Error>> {
+        let dirpath = self.config.get_log_dir_path();
+        match logfile::log_files(self.config) {
+            Ok(files) => {
+                let bench = files.last().and_then(|path| Origin::bench_from_path(self.config, path));
+                let state = get_logs_data(&files, &self.config.definition_fields)?;
+                if let (Some(bench), (_, record::Tags { origin: Some(origin), .. })) = (bench, record::split_fields(&state.last_line)) {
+                    if origin.bench != bench || origin.stand != self.config.stand_nm {
+                        eprintln!("Last record is from other test bench or stand: {}", origin);
+                    }
+                }
+                Ok(state)
+            },
+            Err(e) => {
+                eprintln!("Empty dir or no file: {:?}\nError {:?}", &dirpath, e);
+                Ok(LastLogState::default())
+            },
+        }
+    }
+}
+
+/// Format of timestamps in database with UTC offset, readable by SQLite date functions
+#[cfg(feature = "sqlite")]
+const DB_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%:z";
+
+/// Format of timestamps in database written before UTC offset was stored
+#[cfg(feature = "sqlite")]
+const DB_LOCAL_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS definitions (
+    id INTEGER PRIMARY KEY,
+    time TEXT,
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    host TEXT,
+    interlock TEXT,
+    tr_number TEXT,
+    specimen_id TEXT,
+    request_type TEXT,
+    testing_category TEXT,
+    technician TEXT,
+    available_time TEXT
+);
+CREATE TABLE IF NOT EXISTS definition_fields (
+    definition_id INTEGER NOT NULL REFERENCES definitions(id),
+    name TEXT NOT NULL,
+    value TEXT
+);
+CREATE TABLE IF NOT EXISTS intervals (
+    id INTEGER PRIMARY KEY,
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    host TEXT,
+    kind TEXT NOT NULL,
+    start TEXT,
+    start_id INTEGER,
+    start_fields TEXT,
+    end TEXT,
+    end_id INTEGER,
+    end_fields TEXT,
+    duration INTEGER
+);
+CREATE TABLE IF NOT EXISTS pauses (
+    id INTEGER PRIMARY KEY,
+    interval_id INTEGER REFERENCES intervals(id),
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    host TEXT,
+    start TEXT NOT NULL,
+    start_id INTEGER,
+    reason TEXT,
+    end TEXT,
+    end_id INTEGER,
+    duration INTEGER
+);
+CREATE TABLE IF NOT EXISTS corrections (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    host TEXT,
+    record_id INTEGER,
+    interval_start TEXT NOT NULL,
+    start TEXT NOT NULL,
+    classification TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS interlock (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    host TEXT,
+    record_id INTEGER,
+    state TEXT NOT NULL,
+    reason TEXT
+);
+CREATE TABLE IF NOT EXISTS specimen_events (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    host TEXT,
+    record_id INTEGER,
+    event TEXT NOT NULL,
+    specimen TEXT NOT NULL,
+    reason TEXT
+);
+CREATE TABLE IF NOT EXISTS comments (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    host TEXT,
+    record_id INTEGER,
+    kind TEXT NOT NULL,
+    comment TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS imported_files (
+    path TEXT PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS records (
+    bench TEXT NOT NULL,
+    stand INTEGER NOT NULL,
+    record_id INTEGER NOT NULL,
+    time TEXT NOT NULL,
+    PRIMARY KEY (bench, stand, record_id, time)
+);";
+
+/// SQLite database with intervals, pauses, definitions, corrections, interlock, specimen event and comment tables.
+/// Fields of records are joined by `::` like in text log.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStorage {
+    /// Connection shared by storages of process
+    conn: Arc<Mutex<Connection>>,
+    /// Origin of records without `ORIGIN` tag
+    origin: Origin,
+}
+
+/// Connections to databases of process by path
+#[cfg(feature = "sqlite")]
+static DATABASES: Mutex<BTreeMap<std::path::PathBuf, Arc<Mutex<Connection>>>> = Mutex::new(BTreeMap::new());
+
+/// Interval of stand without end
+#[cfg(feature = "sqlite")]
+struct OpenInterval {
+    id: i64,
+    /// Missing for interval from OUT record without IN record
+    start: Option<LogTime>,
+}
+
+#[cfg(feature = "sqlite")]
+fn db_time(time: LogTime) -> String {
+    time.format(DB_TIME_FORMAT).to_string()
+}
+
+#[cfg(feature = "sqlite")]
+fn parse_db_time(text: &str) -> Result<LogTime, Box<dyn Error>> {
+    match DateTime::parse_from_str(text, DB_TIME_FORMAT) {
+        Ok(time) => Ok(time),
+        Err(_) => Ok(record::resolve_local(NaiveDateTime::parse_from_str(text, DB_LOCAL_TIME_FORMAT)?, &Local, None)),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStorage {
+    /// Storage with connection to database opened once per process
+    pub fn open(path: &std::path::Path, origin: Origin) -> Result<SqliteStorage, Box<dyn Error>> {
+        let mut databases = DATABASES.lock().unwrap();
+        if let Some(conn) = databases.get(path) {
+            return Ok(SqliteStorage { conn: conn.clone(), origin });
+        }
+        let storage = SqliteStorage::new(Connection::open(path)?, origin)?;
+        databases.insert(path.to_owned(), storage.conn.clone());
+        Ok(storage)
+    }
+
+    pub fn new(conn: Connection, origin: Origin) -> Result<SqliteStorage, Box<dyn Error>> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteStorage { conn: Arc::new(Mutex::new(conn)), origin })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    /// Remember record with id, returns false if it is stored already, e.g. written live
+    /// before import of its log file
+    fn store_key(&self, origin: &Origin, record: &Record) -> Result<bool, Box<dyn Error>> {
+        let id = match record.tags.id {
+            Some(id) => id,
+            None => return Ok(true),
+        };
+        let inserted = self.conn().execute("INSERT OR IGNORE INTO records (bench, stand, record_id, time) VALUES (?1, ?2, ?3, ?4)",
+            params![origin.bench, origin.stand, id, db_time(record.time)])?;
+        Ok(inserted > 0)
+    }
+
+    /// Check if record with id is stored already
+    fn is_stored(&self, record: &Record) -> Result<bool, Box<dyn Error>> {
+        let origin = record.tags.origin.as_ref().unwrap_or(&self.origin);
+        let found: Option<i64> = self.conn().query_row(
+            "SELECT stand FROM records WHERE bench = ?1 AND stand = ?2 AND record_id = ?3 AND time = ?4",
+            params![origin.bench, origin.stand, record.tags.id, db_time(record.time)],
+            |row| row.get(0)).optional()?;
+        Ok(found.is_some())
+    }
+
+    fn open_interval(&self, origin: &Origin) -> Result<Option<OpenInterval>, Box<dyn Error>> {
+        let found: Option<(i64, Option<String>)> = self.conn().query_row(
+            "SELECT id, start FROM intervals WHERE bench = ?1 AND stand = ?2 AND end IS NULL ORDER BY id DESC LIMIT 1",
+            params![origin.bench, origin.stand],
+            |row| Ok((row.get(0)?, row.get(1)?))).optional()?;
+        match found {
+            Some((id, Some(start))) => Ok(Some(OpenInterval { id, start: Some(parse_db_time(&start)?) })),
+            Some((id, None)) => Ok(Some(OpenInterval { id, start: None })),
+            None => Ok(None),
+        }
+    }
+
+    /// End pause of interval which was not resumed
+    fn end_pauses(&self, interval_id: i64, time: LogTime) -> Result<(), Box<dyn Error>> {
+        self.conn().execute("UPDATE pauses SET end = ?1 WHERE interval_id = ?2 AND end IS NULL",
+            params![db_time(time), interval_id])?;
+        Ok(())
+    }
+
+    fn close_interval(&self, id: i64, record: &Record, duration: Option<i64>) -> Result<(), Box<dyn Error>> {
+        self.end_pauses(id, record.time)?;
+        self.conn().execute(
+            "UPDATE intervals SET end = ?1, end_id = ?2, end_fields = ?3, duration = ?4 WHERE id = ?5",
+            params![db_time(record.time), record.tags.id, record::join_fields(&record.fields), duration, id])?;
+        Ok(())
+    }
+
+    fn insert_definition(&self, time: Option<LogTime>, origin: &Origin, definition: &TestDefinition) -> Result<(), Box<dyn Error>> {
+        self.conn().execute(
+            "INSERT INTO definitions (time, bench, stand, host, interlock, tr_number, specimen_id, request_type, testing_category, technician, available_time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![time.map(db_time), origin.bench, origin.stand, origin.host,
+                definition.interlock_status, definition.tr_number, definition.specimen_id, definition.request_type,
+                definition.testing_category, definition.technician, definition.available_time])?;
+        let id = self.conn().last_insert_rowid();
+        for (name, value) in definition.fields.iter() {
+            self.conn().execute("INSERT INTO definition_fields (definition_id, name, value) VALUES (?1, ?2, ?3)",
+                params![id, name, value])?;
+        }
+        Ok(())
+    }
+
+    /// Import content of text log file, records without origin are from `origin`
+    pub fn import_content(&mut self, contents: &str, origin: &Origin) -> Result<usize, Box<dyn Error>> {
+        let fallback = std::mem::replace(&mut self.origin, origin.clone());
+        let res = self.import_lines(record::parse_lines(contents)?, origin);
+        self.origin = fallback;
+        res
+    }
+
+    fn import_lines(&mut self, lines: Vec<Line>, origin: &Origin) -> Result<usize, Box<dyn Error>> {
+        let mut count = 0;
+        let mut definition: Vec<(String, String)> = Vec::new();
+
+        let mut lines = lines.into_iter().peekable();
+        while let Some(line) = lines.next() {
+            match line {
+                Line::Definition(key, value) => definition.push((key, value)),
+                // Record written live is not imported again
+                Line::Record(record) => if !self.is_stored(&record)? {
+                    self.append_record(record)?;
+                    count += 1;
+                },
+                Line::Version(_) | Line::Unknown(_) => (),
+            }
+            let block_end = !matches!(lines.peek(), Some(Line::Definition(..)));
+            if block_end && !definition.is_empty() {
+                // Definition block has no timestamp, time of following record is used
+                let (time, written) = match lines.peek() {
+                    Some(Line::Record(record)) => (Some(record.time), self.is_stored(record)?),
+                    _ => (None, false),
+                };
+                // Definition written live is followed by record written live
+                if written {
+                    definition.clear();
+                    continue;
+                }
+                let values: HashMap<&str, String> = definition.iter().map(|(k, v)| (k.as_str(), v.to_owned())).collect();
+                let mut block = TestDefinition::default();
+                for (key, value) in definition.iter() {
+                    let origin_key = ["TestBench ID", "Stand Number", "Host Name"].contains(&key.as_str());
+                    if !origin_key && !block.set(key, value.to_owned()) {
+                        block.fields.insert(key.to_owned(), value.to_owned());
+                    }
+                }
+                let block_origin = Origin {
+                    bench: values.get("TestBench ID").cloned().unwrap_or_else(|| origin.bench.to_owned()),
+                    stand: values.get("Stand Number").and_then(|s| s.parse().ok()).unwrap_or(origin.stand),
+                    host: values.get("Host Name").cloned().unwrap_or_else(|| origin.host.to_owned()),
+                };
+                self.insert_definition(time, &block_origin, &block)?;
+                definition.clear();
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Storage for SqliteStorage {
+    fn append_record(&mut self, mut record: Record) -> Result<Record, Box<dyn Error>> {
+        let origin = record.tags.origin.clone().unwrap_or_else(|| self.origin.clone());
+        if !self.store_key(&origin, &record)? {
+            return Ok(record);
+        }
+        match record.kind {
+            RecordKind::In => {
+                // Missing OUT record, previous interval ends with new one
+                if let Some(open) = self.open_interval(&origin)? {
+                    let duration = open.start.map(|start| (record.time - start).num_seconds());
+                    self.end_pauses(open.id, record.time)?;
+                    self.conn().execute("UPDATE intervals SET end = ?1, duration = ?2 WHERE id = ?3",
+                        params![db_time(record.time), duration, open.id])?;
+                }
+                let kind = if record.fields[0] == "Test Start" { "test" } else { "loss" };
+                self.conn().execute(
+                    "INSERT INTO intervals (bench, stand, host, kind, start, start_id, start_fields) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![origin.bench, origin.stand, origin.host, kind, db_time(record.time), record.tags.id, record::join_fields(&record.fields)])?;
+            },
+            RecordKind::Out => match self.open_interval(&origin)? {
+                Some(open) => {
+                    if record.tags.duration.is_none() {
+                        record.tags.duration = open.start.map(|start| (record.time - start).num_seconds());
+                    }
+                    self.close_interval(open.id, &record, record.tags.duration)?;
+                },
+                None => {
+                    let kind = if record.fields[0] == "Test Stopped" { "test" } else { "loss" };
+                    self.conn().execute(
+                        "INSERT INTO intervals (bench, stand, host, kind) VALUES (?1, ?2, ?3, ?4)",
+                        params![origin.bench, origin.stand, origin.host, kind])?;
+                    let id = self.conn().last_insert_rowid();
+                    self.close_interval(id, &record, record.tags.duration)?;
+                },
+            },
+            RecordKind::Split => {
+                let time = |text: &str| -> Result<String, Box<dyn Error>> {
+                    Ok(db_time(record::parse_time(text)?))
+                };
+                if record.fields.len() < 3 {
+                    return Err(From::from(format!("Invalid SPLIT record: {}", record.to_line(record::MADL_VERSION))));
+                }
+                self.conn().execute(
+                    "INSERT INTO corrections (time, bench, stand, host, record_id, interval_start, start, classification)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![db_time(record.time), origin.bench, origin.stand, origin.host, record.tags.id,
+                        time(&record.fields[0])?, time(&record.fields[1])?, record::join_fields(&record.fields[2..])])?;
+            },
+            RecordKind::Interlock => {
+                self.conn().execute(
+                    "INSERT INTO interlock (time, bench, stand, host, record_id, state, reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![db_time(record.time), origin.bench, origin.stand, origin.host, record.tags.id,
+                        record.fields[0], record.fields.get(1)])?;
+            },
+            RecordKind::Pause => {
+                let interval_id = self.open_interval(&origin)?.map(|open| open.id);
+                // New pause of paused test changes reason
+                if let Some(id) = interval_id {
+                    self.end_pauses(id, record.time)?;
+                }
+                self.conn().execute(
+                    "INSERT INTO pauses (interval_id, bench, stand, host, start, start_id, reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![interval_id, origin.bench, origin.stand, origin.host, db_time(record.time), record.tags.id,
+                        record::join_fields(&record.fields[1..])])?;
+            },
+            RecordKind::Resume => {
+                let open: Option<(i64, String)> = self.conn().query_row(
+                    "SELECT id, start FROM pauses WHERE bench = ?1 AND stand = ?2 AND end IS NULL ORDER BY id DESC LIMIT 1",
+                    params![origin.bench, origin.stand],
+                    |row| Ok((row.get(0)?, row.get(1)?))).optional()?;
+                if let Some((id, start)) = open {
+                    if record.tags.duration.is_none() {
+                        record.tags.duration = Some((record.time - parse_db_time(&start)?).num_seconds());
+                    }
+                    self.conn().execute("UPDATE pauses SET end = ?1, end_id = ?2, duration = ?3 WHERE id = ?4",
+                        params![db_time(record.time), record.tags.id, record.tags.duration, id])?;
+                }
+            },
+            RecordKind::Specimen => {
+                if record.fields.len() < 2 {
+                    return Err(From::from(format!("Invalid SPECIMEN record: {}", record.to_line(record::MADL_VERSION))));
+                }
+                self.conn().execute(
+                    "INSERT INTO specimen_events (time, bench, stand, host, record_id, event, specimen, reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![db_time(record.time), origin.bench, origin.stand, origin.host, record.tags.id,
+                        record.fields[0], record.fields[1], record.fields.get(2)])?;
+            },
+        }
+        if let Some(comment) = &record.tags.comment {
+            self.conn().execute(
+                "INSERT INTO comments (time, bench, stand, host, record_id, kind, comment) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![db_time(record.time), origin.bench, origin.stand, origin.host, record.tags.id, record.kind.name(), comment])?;
+        }
+        Ok(record)
+    }
+
+    fn write_definition(&mut self, definition: &TestDefinition, origin: &Origin, interlock: Interlock) -> Result<(), Box<dyn Error>> {
+        let definition = TestDefinition { interlock_status: interlock.to_string(), ..definition.clone() };
+        self.insert_definition(Some(record::now()), origin, &definition)
+    }
+
+    fn log_data(&self) -> Result<LastLogState, Box<dyn Error>> {
+        let origin = &self.origin;
+        let mut state = LastLogState::default();
+        let definition = self.conn().query_row(
+            "SELECT interlock, tr_number, specimen_id, request_type, testing_category, technician, available_time, id
+             FROM definitions WHERE bench = ?1 AND stand = ?2 ORDER BY id DESC LIMIT 1",
+            params![origin.bench, origin.stand],
+            |row| {
+                let value = |i: usize| row.get::<_, Option<String>>(i).map(Option::unwrap_or_default);
+                Ok((TestDefinition {
+                    interlock_status: value(0)?,
+                    tr_number: value(1)?,
+                    specimen_id: value(2)?,
+                    request_type: value(3)?,
+                    testing_category: value(4)?,
+                    technician: value(5)?,
+                    available_time: value(6)?,
+                    ..Default::default()
+                }, row.get::<_, i64>(7)?))
+            }).optional()?;
+        if let Some((mut definition, id)) = definition {
+            let conn = self.conn();
+            let mut stmt = conn.prepare("SELECT name, value FROM definition_fields WHERE definition_id = ?1")?;
+            let rows = stmt.query_map(params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)))?;
+            for row in rows {
+                let (name, value) = row?;
+                definition.fields.insert(name, value.unwrap_or_default());
+            }
+            state.definition = definition;
+        }
+
+        let last = self.conn().query_row(
+            "SELECT start, start_id, start_fields, end, end_id, end_fields, host, id FROM intervals
+             WHERE bench = ?1 AND stand = ?2 ORDER BY id DESC LIMIT 1",
+            params![origin.bench, origin.stand],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<u64>>(1)?, row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?, row.get::<_, Option<u64>>(4)?, row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<String>>(6)?, row.get::<_, i64>(7)?))).optional()?;
+        let (kind, time, id, fields, host) = match last {
+            Some((Some(start), start_id, start_fields, None, _, _, host, interval_id)) => {
+                // Open interval can be paused
+                let pause = self.conn().query_row(
+                    "SELECT start, start_id, reason, host FROM pauses WHERE interval_id = ?1 AND end IS NULL ORDER BY id DESC LIMIT 1",
+                    params![interval_id],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<u64>>(1)?, row.get::<_, Option<String>>(2)?,
+                        row.get::<_, Option<String>>(3)?))).optional()?;
+                match pause {
+                    Some((pause_start, pause_id, reason, pause_host)) => {
+                        let fields = match reason.filter(|r| !r.is_empty()) {
+                            Some(reason) => format!("Test Paused::{}", reason),
+                            None => "Test Paused".to_string(),
+                        };
+                        (RecordKind::Pause, pause_start, pause_id, Some(fields), pause_host)
+                    },
+                    None => (RecordKind::In, start, start_id, start_fields, host),
+                }
+            },
+            Some((_, _, _, Some(end), end_id, end_fields, host, _)) => (RecordKind::Out, end, end_id, end_fields, host),
+            _ => return Ok(state),
+        };
+        let record = Record {
+            kind,
+            time: parse_db_time(&time)?,
+            fields: record::split_joined(&fields.unwrap_or_default()),
+            tags: record::Tags {
+                id,
+                origin: Some(Origin { host: host.unwrap_or_default(), ..origin.clone() }),
+                ..Default::default()
+            },
+        };
+        state.last_line = record.to_line(record::MADL_VERSION);
+        Ok(state)
+    }
+}
+
+/// Import archived and current text log files of stand to configured database, already imported files are skipped
+#[cfg(feature = "sqlite")]
+pub fn import_logs(config: &Config) -> Result<(), Box<dyn Error>> {
+    let database = match &config.storage {
+        StorageBackend::Sqlite { database } => database,
+        StorageBackend::Text => return Err(From::from("Storage in madl.cfg is not Sqlite, nothing to import to")),
+    };
+    let origin = Origin::new(config)?;
+    let mut storage = SqliteStorage::open(database, origin.clone())?;
+
+    let mut count = 0;
+    // Records are imported in order of writing, archived logs first
+    for (name, contents) in logfile::read_logs(config)? {
+        // Log is recorded by file name, so it is not imported again after it is archived
+        let file_name = name.rsplit(['/', '\\']).next().unwrap_or(&name).to_string();
+        let legacy_name = config.get_log_dir_path().join(&file_name).display().to_string();
+        let imported: Option<String> = storage.conn()
+            .query_row("SELECT path FROM imported_files WHERE path = ?1 OR path = ?2", params![file_name, legacy_name], |row| row.get(0))
+            .optional()?;
+        if imported.is_some() {
+            continue;
+        }
+        let bench = Origin::bench_from_path(config, std::path::Path::new(&file_name));
+        let file_origin = Origin { bench: bench.unwrap_or_else(|| origin.bench.to_owned()), ..origin.clone() };
+        storage.conn().execute_batch("BEGIN")?;
+        let res = storage.import_content(&contents, &file_origin)
+            .and_then(|lines| Ok(storage.conn().execute("INSERT INTO imported_files (path) VALUES (?1)", params![file_name]).map(|_| lines)?));
+        match res {
+            Ok(lines) => {
+                storage.conn().execute_batch("COMMIT")?;
+                println!("Imported {} records and definitions: {}", lines, name);
+                count += 1;
+            },
+            Err(e) => {
+                storage.conn().execute_batch("ROLLBACK")?;
+                println!("Skipped {}: {}", name, e);
+            },
+        }
+    }
+    println!("\nImported {} log files to: {}", count, database.display());
+
+    Ok(())
+}
+
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const LOG: &str = "\
+MADL_Version::3.1\r\n\
+TestBench ID::TB1\r\n\
+Stand Number::1\r\n\
+TR_Number::TR1\r\n\
+Specimen ID::S1\r\n\
+Firmware::1.2\r\n\
+IN::2020-02-01T08:00:00+01:00::Test Start::ID=1\r\n\
+OUT::2020-02-01T10:00:00+01:00::Test Stopped::Finished::none::ID=2\r\n\
+IN::2020-02-01T10:00:00+01:00::Idle Time::No test sample::Sample Shortage::ID=3\r\n";
+
+    #[test]
+    fn test_sqlite_pause() {
+        let origin = Origin { bench: "TB1".to_string(), stand: 1, host: "PC1".to_string() };
+        let mut storage = SqliteStorage::new(Connection::open_in_memory().unwrap(), origin.clone()).unwrap();
+        let log = "\
+MADL_Version::3.1\r\n\
+IN::2020-02-01T08:00:00+01:00::Test Start::ID=1\r\n\
+PAUSE::2020-02-01T09:00:00+01:00::Test Paused::Idle Time::No test sample::Sample Shortage::ID=2\r\n";
+        storage.import_content(log, &origin).unwrap();
+        assert_eq!(storage.log_data().unwrap().last_line,
+            "PAUSE::2020-02-01T09:00:00+01:00::Test Paused::Idle Time::No test sample::Sample Shortage::ID=2::ORIGIN=TB1;1;PC1");
+
+        storage.import_content("MADL_Version::3.1\r\nRESUME::2020-02-01T09:30:00+01:00::Test Resumed::ID=3\r\n", &origin).unwrap();
+        assert!(storage.log_data().unwrap().last_line.starts_with("IN::2020-02-01T08:00:00+01:00::Test Start"));
+        let duration: i64 = storage.conn().query_row("SELECT duration FROM pauses", [], |row| row.get(0)).unwrap();
+        assert_eq!(duration, 1800);
+    }
+
+    #[test]
+    fn test_sqlite_storage() {
+        let origin = Origin { bench: "TB1".to_string(), stand: 1, host: "PC1".to_string() };
+        let mut storage = SqliteStorage::new(Connection::open_in_memory().unwrap(), origin.clone()).unwrap();
+        assert_eq!(storage.import_content(LOG, &origin).unwrap(), 4);
+
+        let state = storage.log_data().unwrap();
+        assert_eq!(state.definition.tr_number, "TR1");
+        assert_eq!(state.definition.fields["Firmware"], "1.2");
+        assert_eq!(state.last_line, "IN::2020-02-01T10:00:00+01:00::Idle Time::No test sample::Sample Shortage::ID=3::ORIGIN=TB1;1;PC1");
+
+        let duration: i64 = storage.conn().query_row("SELECT duration FROM intervals WHERE kind = 'test'", [], |row| row.get(0)).unwrap();
+        assert_eq!(duration, 7200);
+    }
+
+    #[test]
+    fn test_import_written_records() {
+        let origin = Origin { bench: "TB1".to_string(), stand: 1, host: "PC1".to_string() };
+        let mut storage = SqliteStorage::new(Connection::open_in_memory().unwrap(), origin.clone()).unwrap();
+        // Definition and first record written live, log file imported later
+        let lines = record::parse_lines(LOG).unwrap();
+        storage.write_definition(&TestDefinition { tr_number: "TR1".to_string(), ..Default::default() }, &origin, Interlock::Enabled).unwrap();
+        if let Line::Record(record) = &lines[6] {
+            storage.append_record(record.clone()).unwrap();
+        }
+        assert_eq!(storage.import_content(LOG, &origin).unwrap(), 2);
+
+        let count = |table: &str| -> i64 {
+            storage.conn().query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+        };
+        assert_eq!((count("definitions"), count("intervals")), (1, 2));
+    }
+
+    #[test]
+    fn test_import_archived_logs() {
+        let mut config = crate::tests::temp_stand("import");
+        std::fs::create_dir_all(config.get_log_dir_path()).unwrap();
+        let day = Local.with_ymd_and_hms(2020, 2, 1, 12, 0, 0).unwrap();
+        std::fs::write(config.get_log_file_path(day).unwrap(), LOG).unwrap();
+        // Latest log is not archived
+        std::fs::write(config.get_log_file_path(day + chrono::Duration::days(1)).unwrap(),
+            "MADL_Version::3.1\r\nOUT::2020-02-02T08:00:00+01:00::Idle Time::No test sample::Sample Shortage::ID=4\r\n").unwrap();
+        logfile::archive(&config, Some(1)).unwrap();
+        assert!(!config.get_log_file_path(day).unwrap().exists());
+        let database = config.settings_dir.join("madl.db");
+        config.storage = StorageBackend::Sqlite { database: database.clone() };
+
+        // Second import skips log imported before
+        import_logs(&config).unwrap();
+        import_logs(&config).unwrap();
+        let intervals: i64 = SqliteStorage::open(&database, Origin::new(&config).unwrap()).unwrap().conn()
+            .query_row("SELECT COUNT(*) FROM intervals", [], |row| row.get(0)).unwrap();
+        std::fs::remove_dir_all(&config.settings_dir).unwrap();
+        assert_eq!(intervals, 2);
+    }
+}
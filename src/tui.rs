@@ -0,0 +1,445 @@
+use std::error::Error;
+use std::sync::mpsc;
+use std::time::{Duration as StdDuration, Instant};
+use chrono::Duration;
+use hotwatch::{Hotwatch, Event as WatchEvent};
+use ratatui::{DefaultTerminal, Frame};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+
+use crate::{Config, Laststate, TcState, Overrun, TestProgress, test_progress, overrun_warning, read_last_state, check_state,
+    read_tc_log, read_text_file, resume_test, logfile, update_interlock, lang, record, report, LOG_TIME_FORMAT};
+use crate::lang::Answer;
+use crate::record::LogTime;
+use crate::workflow::{self, Prompt};
+
+const MENU: [&str; 5] = [
+    "d - Define test",
+    "c - Change time loss classification",
+    "s - Start test duration measurement",
+    "p - Pause/resume test",
+    "e - Exit",
+];
+
+/// Number of records shown in log pane
+const LOG_LINES: usize = 10;
+
+/// Period of reading stand status and interlock
+const REFRESH_INTERVAL: StdDuration = StdDuration::from_secs(2);
+
+/// Period of reading test time of running TR from all logs
+const PROGRESS_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// Dialog drawn over main menu
+enum Dialog {
+    Select { title: String, items: Vec<String>, state: ListState },
+    Input { title: String, value: String },
+    Confirm { title: String, lines: Vec<String>, yes: bool },
+}
+
+/// Stand status shown in status panel
+#[derive(Default)]
+struct Status {
+    state: String,
+    detail: String,
+    since: Option<LogTime>,
+    tr: String,
+    specimen: String,
+    available: String,
+    interlock: String,
+    log: Vec<String>,
+}
+
+struct App {
+    config: Config,
+    status: Status,
+    menu: ListState,
+    message: String,
+    measurement: Option<(Hotwatch, mpsc::Receiver<TcState>)>,
+    progress: Option<TestProgress>,
+    progress_read: Option<Instant>,
+    refreshed: Option<Instant>,
+    warned: Overrun,
+}
+
+/// Read pressed key, `None` if no key was pressed in refresh interval
+fn poll_key() -> Result<Option<KeyCode>, Box<dyn Error>> {
+    if !event::poll(StdDuration::from_millis(250))? {
+        return Ok(None);
+    }
+    match event::read()? {
+        Event::Key(key) if key.kind == KeyEventKind::Press => Ok(Some(key.code)),
+        _ => Ok(None),
+    }
+}
+
+/// Rectangle in the middle of area
+fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Percentage(percent_y)]).flex(Flex::Center).areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center).areas(area);
+    area
+}
+
+impl App {
+    fn new(config: &Config) -> App {
+        let mut menu = ListState::default();
+        menu.select(Some(0));
+        App {
+            config: config.clone(),
+            status: Status::default(),
+            menu,
+            message: String::new(),
+            measurement: None,
+            progress: None,
+            progress_read: None,
+            refreshed: None,
+            warned: Overrun::None,
+        }
+    }
+
+    /// Read stand status at most once in refresh interval, error is shown in status line
+    fn refresh(&mut self) {
+        if self.refreshed.is_some_and(|t| t.elapsed() < REFRESH_INTERVAL) {
+            return;
+        }
+        self.refreshed = Some(Instant::now());
+        if let Err(e) = self.read_status() {
+            self.message = format!("Status not read: {}", e);
+        }
+    }
+
+    /// Read stand status from log files and interlock source
+    fn read_status(&mut self) -> Result<(), Box<dyn Error>> {
+        let state = read_last_state(&self.config)?;
+        let mut status = Status {
+            tr: state.definition.tr_number.to_owned(),
+            specimen: state.definition.specimen_id.to_owned(),
+            available: state.definition.available_time.to_owned(),
+            ..Default::default()
+        };
+        match check_state(&state) {
+            Laststate::IN(vec_data) if vec_data[0].contains("Test Start") => status.state = "Testing".to_string(),
+            Laststate::IN(vec_data) => {
+                status.state = "Time loss".to_string();
+                status.detail = vec_data.join(" / ");
+            },
+            Laststate::OUT(vec_data) => {
+                status.state = "Stopped".to_string();
+                status.detail = vec_data.join(" / ");
+            },
+            Laststate::PAUSE(vec_data) => {
+                status.state = "Paused".to_string();
+                status.detail = vec_data.join(" / ");
+            },
+            Laststate::EMPTY => status.state = "No record".to_string(),
+        }
+
+        if let Ok(path) = logfile::latest_log(&self.config) {
+            if path.exists() {
+                let contents = read_text_file(&path)?;
+                if let Ok(lines) = record::parse_lines(&contents) {
+                    status.since = record::open_in(&lines).map(|r| r.time);
+                }
+                let lines: Vec<&str> = contents.lines().collect();
+                status.log = lines[lines.len().saturating_sub(LOG_LINES)..].iter()
+                    .map(|l| record::split_fields(l).0.join("::"))
+                    .collect();
+            }
+        }
+
+        status.interlock = match update_interlock(&self.config) {
+            Ok((state, true)) => {
+                self.message = format!("Interlock {}!", state);
+                state.to_string()
+            },
+            Ok((state, false)) => state.to_string(),
+            Err(e) => format!("unknown ({})", e),
+        };
+        // Test time is read again after change of state
+        if status.state != self.status.state || self.progress_read.is_none_or(|t| t.elapsed() >= PROGRESS_INTERVAL) {
+            self.progress = test_progress(&self.config)?;
+            self.progress_read = Some(Instant::now());
+        }
+        match &self.progress {
+            Some(progress) => {
+                if let Some(text) = overrun_warning(&self.config, progress, &mut self.warned) {
+                    self.message = text;
+                }
+            },
+            None => self.warned = Overrun::None,
+        }
+        self.status = status;
+        Ok(())
+    }
+
+    fn draw(&self, f: &mut Frame, dialog: Option<&mut Dialog>) {
+        let [status_area, menu_area, log_area] = Layout::vertical([
+            Constraint::Length(8),
+            Constraint::Min(7),
+            Constraint::Length(LOG_LINES as u16 + 2),
+        ]).areas(f.area());
+
+        let now = record::now();
+        let status = &self.status;
+        let (since, elapsed) = match status.since {
+            Some(since) => (since.format(LOG_TIME_FORMAT).to_string(), report::format_duration(now - since)),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let progress = match &self.progress {
+            Some(progress) => {
+                let elapsed = progress.elapsed_at(now);
+                let style = match progress.overrun(now, Duration::minutes(self.config.overrun_warning_minutes)) {
+                    Overrun::None => Style::default(),
+                    Overrun::Approaching => Style::default().fg(Color::Yellow),
+                    Overrun::Exceeded => Style::default().fg(Color::Red),
+                };
+                Line::styled(format!("Test time of TR: {} of planned {}", report::format_duration(elapsed),
+                    report::format_duration(progress.planned)), style)
+            },
+            None => Line::from(""),
+        };
+        let measurement = match self.measurement {
+            Some(_) => "watching TC log",
+            None => "not running",
+        };
+        let text = vec!(
+            Line::from(format!("Stand: {}   Interlock: {}   Measurement: {}", self.config.stand_nm, status.interlock, measurement)),
+            Line::from(format!("State: {}   {}", status.state, status.detail)),
+            Line::from(format!("Since: {}   Elapsed: {}", since, elapsed)),
+            Line::from(format!("TR: {}   Specimen: {}   Available time: {}", status.tr, status.specimen, status.available)),
+            progress,
+            Line::styled(self.message.as_str(), Style::default().fg(Color::Yellow)),
+        );
+        let block = Block::default().borders(Borders::ALL).title(" MADL stand status ");
+        f.render_widget(Paragraph::new(text).block(block), status_area);
+
+        let items: Vec<ListItem> = MENU.iter().map(|m| ListItem::new(*m)).collect();
+        let menu = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Menu "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(menu, menu_area, &mut self.menu.clone());
+
+        let log: Vec<ListItem> = status.log.iter().map(|l| ListItem::new(l.as_str())).collect();
+        f.render_widget(List::new(log).block(Block::default().borders(Borders::ALL).title(" Log ")), log_area);
+
+        if let Some(dialog) = dialog {
+            let area = popup_area(f.area(), 70, 60);
+            f.render_widget(Clear, area);
+            match dialog {
+                Dialog::Select { title, items, state } => {
+                    let items: Vec<ListItem> = items.iter().enumerate()
+                        .map(|(i, v)| ListItem::new(format!("{}. {}", i, v)))
+                        .collect();
+                    let list = List::new(items)
+                        .block(Block::default().borders(Borders::ALL).title(format!(" {} ", title)))
+                        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                    f.render_stateful_widget(list, area, state);
+                },
+                Dialog::Input { title, value } => {
+                    let text = vec!(Line::from(format!(">>{}_", value)), Line::from(""), Line::from("Enter - confirm, Esc - cancel"));
+                    let block = Block::default().borders(Borders::ALL).title(format!(" {} ", title));
+                    f.render_widget(Paragraph::new(text).block(block), area);
+                },
+                Dialog::Confirm { title, lines, yes } => {
+                    let mut text: Vec<Line> = lines.iter().map(|l| Line::from(l.as_str())).collect();
+                    let (yes_style, no_style) = match yes {
+                        true => (Style::default().add_modifier(Modifier::REVERSED), Style::default()),
+                        false => (Style::default(), Style::default().add_modifier(Modifier::REVERSED)),
+                    };
+                    text.push(Line::from(""));
+                    text.push(Line::from(vec!(
+                        ratatui::text::Span::styled(" Yes ", yes_style),
+                        ratatui::text::Span::raw("   "),
+                        ratatui::text::Span::styled(" No ", no_style),
+                    )));
+                    let block = Block::default().borders(Borders::ALL).title(format!(" {} ", title));
+                    f.render_widget(Paragraph::new(text).block(block).wrap(Wrap { trim: false }), area);
+                },
+            }
+        }
+    }
+
+    fn start_measurement(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.measurement.is_some() {
+            self.message = "Measurement is already running.".to_string();
+            return Ok(());
+        }
+        let (tx, rx) = mpsc::channel();
+        let mut hotwatch = Hotwatch::new()?;
+        hotwatch.watch(self.config.get_tc_log_folder_path(), move |event: WatchEvent| {
+            if let WatchEvent::Write(path) = event {
+                let _ = tx.send(read_tc_log(path));
+            }
+        })?;
+        self.measurement = Some((hotwatch, rx));
+        self.message = "Waiting for test start in TC log.".to_string();
+        Ok(())
+    }
+
+    /// Pause running test or resume paused test
+    fn pause_resume(config: &Config, prompt: &mut dyn Prompt) -> Result<(), Box<dyn Error>> {
+        if workflow::pause_test(config, prompt)? {
+            prompt.message(&lang::text("test_paused"));
+        } else if resume_test(config)? {
+            prompt.message(&lang::text("test_resumed"));
+        } else {
+            prompt.message(&lang::text("no_test_running"));
+        }
+        Ok(())
+    }
+
+    fn main_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        let config = self.config.clone();
+        loop {
+            let received = self.measurement.as_ref().and_then(|(_, rx)| rx.try_recv().ok());
+            if let Some(received) = received {
+                match workflow::handle_tc(&config, &mut Screen { app: self, terminal }, received) {
+                    Ok(true) => (),
+                    Ok(false) => self.measurement = None,
+                    Err(e) => self.message = format!("Application error: {}", e),
+                }
+                self.refreshed = None;
+            }
+
+            self.refresh();
+            terminal.draw(|f| self.draw(f, None))?;
+            let action = match poll_key()? {
+                Some(KeyCode::Up) => {
+                    self.menu.select_previous();
+                    continue;
+                },
+                Some(KeyCode::Down) => {
+                    if self.menu.selected().is_some_and(|i| i + 1 < MENU.len()) {
+                        self.menu.select_next();
+                    }
+                    continue;
+                },
+                Some(KeyCode::Enter) => self.menu.selected().unwrap_or(0),
+                Some(KeyCode::Char('d')) => 0,
+                Some(KeyCode::Char('c')) => 1,
+                Some(KeyCode::Char('s')) => 2,
+                Some(KeyCode::Char('p')) => 3,
+                Some(KeyCode::Char('e')) | Some(KeyCode::Char('q')) | Some(KeyCode::Esc) => 4,
+                _ => continue,
+            };
+            self.message.clear();
+            let mut screen = Screen { app: self, terminal };
+            let res = match action {
+                0 => workflow::define_test(&config, &mut screen).map(|_| ()),
+                1 => workflow::change_timeloss(&config, &mut screen),
+                2 => self.start_measurement(),
+                3 => App::pause_resume(&config, &mut screen),
+                _ => return Ok(()),
+            };
+            if let Err(e) = res {
+                self.message = format!("Application error: {}", e);
+            }
+            self.refreshed = None;
+        }
+    }
+}
+
+/// Dialogs of workflow drawn over stand status
+struct Screen<'a> {
+    app: &'a mut App,
+    terminal: &'a mut DefaultTerminal,
+}
+
+impl Screen<'_> {
+    /// Show dialog until it is confirmed with Enter (true) or cancelled with Esc (false)
+    fn run_dialog(&mut self, dialog: &mut Dialog) -> Result<bool, Box<dyn Error>> {
+        loop {
+            self.app.refresh();
+            let app = &*self.app;
+            self.terminal.draw(|f| app.draw(f, Some(dialog)))?;
+            let code = match poll_key()? {
+                Some(code) => code,
+                None => continue,
+            };
+            match (code, &mut *dialog) {
+                (KeyCode::Enter, _) => return Ok(true),
+                (KeyCode::Esc, _) => return Ok(false),
+                (KeyCode::Up, Dialog::Select { state, .. }) => state.select_previous(),
+                (KeyCode::Down, Dialog::Select { state, items, .. }) => {
+                    if state.selected().is_some_and(|i| i + 1 < items.len()) {
+                        state.select_next();
+                    }
+                },
+                (KeyCode::Char(c), Dialog::Select { state, items, .. }) => {
+                    if let Some(num) = c.to_digit(10).filter(|n| (*n as usize) < items.len()) {
+                        state.select(Some(num as usize));
+                    }
+                },
+                (KeyCode::Char(c), Dialog::Input { value, .. }) => value.push(c),
+                (KeyCode::Backspace, Dialog::Input { value, .. }) => {
+                    value.pop();
+                },
+                (KeyCode::Left, Dialog::Confirm { yes, .. }) | (KeyCode::Right, Dialog::Confirm { yes, .. }) => *yes = !*yes,
+                (KeyCode::Char('y'), Dialog::Confirm { yes, .. }) => *yes = true,
+                (KeyCode::Char('n'), Dialog::Confirm { yes, .. }) => *yes = false,
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Prompt for Screen<'_> {
+    fn select(&mut self, title: &str, items: &[String]) -> Result<Option<usize>, Box<dyn Error>> {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        let mut state = ListState::default();
+        state.select(Some(0));
+        let mut dialog = Dialog::Select { title: title.to_string(), items: items.to_vec(), state };
+        if !self.run_dialog(&mut dialog)? {
+            return Ok(None);
+        }
+        match dialog {
+            Dialog::Select { state, .. } => Ok(state.selected()),
+            _ => Ok(None),
+        }
+    }
+
+    fn input(&mut self, title: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let mut dialog = Dialog::Input { title: title.to_string(), value: String::new() };
+        if !self.run_dialog(&mut dialog)? {
+            return Ok(None);
+        }
+        match dialog {
+            Dialog::Input { value, .. } => Ok(Some(value.trim().to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    fn answer(&mut self, question: &str, expected: &[Answer]) -> Result<Option<Answer>, Box<dyn Error>> {
+        let items: Vec<String> = expected.iter().map(|a| a.label()).collect();
+        Ok(self.select(question, &items)?.map(|i| expected[i]))
+    }
+
+    fn confirm(&mut self, title: &str, lines: &[String]) -> Result<bool, Box<dyn Error>> {
+        let mut dialog = Dialog::Confirm { title: title.to_string(), lines: lines.to_vec(), yes: true };
+        if !self.run_dialog(&mut dialog)? {
+            return Ok(false);
+        }
+        match dialog {
+            Dialog::Confirm { yes, .. } => Ok(yes),
+            _ => Ok(false),
+        }
+    }
+
+    fn message(&mut self, text: &str) {
+        self.app.message = text.to_string();
+    }
+}
+
+/// Run full-screen terminal UI
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut terminal = ratatui::try_init()?;
+    let mut app = App::new(config);
+    let res = app.main_loop(&mut terminal);
+    ratatui::restore();
+    res
+}
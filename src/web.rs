@@ -0,0 +1,170 @@
+use std::error::Error;
+use std::io::Cursor;
+use chrono::Timelike;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{Config, report};
+#[cfg(feature = "api")]
+use crate::api;
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::report::StandStatus;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Status of all stands, stand with unreadable log is reported as error
+fn all_status(config: &Config) -> Result<Vec<Result<StandStatus, String>>, Box<dyn Error>> {
+    Ok(config.all_stands()?
+        .iter()
+        .map(|c| report::stand_status(c).map_err(|e| format!("Stand {}: {}", c.stand_nm, e)))
+        .collect())
+}
+
+/// Timeline of one day as bar with test and time loss intervals
+fn timeline_html(status: &StandStatus) -> String {
+    let mut out = String::from("<div class=\"timeline\">");
+    for entry in status.timeline.iter() {
+        // Position from time of day in percent
+        let left = entry.start_time.num_seconds_from_midnight() as f64 / 864.0;
+        let width = (entry.seconds as f64 / 864.0).min(100.0 - left);
+        out.push_str(&format!("<div class=\"{}\" style=\"left:{:.2}%;width:{:.2}%\" title=\"{} {}\"></div>",
+            entry.kind, left, width, escape(&entry.start), escape(&entry.label)));
+    }
+    out.push_str("</div>");
+    out
+}
+
+fn stand_html(status: &StandStatus) -> String {
+    let mut losses = String::new();
+    for (label, seconds) in status.kpi.loss_by_class.iter() {
+        losses.push_str(&format!("<li>{}: {}</li>", escape(label), report::format_duration(chrono::Duration::seconds(*seconds))));
+    }
+    format!("<section><h2>Stand {} - {}</h2>\
+<p class=\"state {}\">{} {}</p>\
+<p>TR: {} Specimen: {}</p>\
+{}\
+<p>Test: {} Time loss: {} Utilization: {:.1} %</p><ul>{}</ul></section>",
+        status.stand, escape(&status.bench),
+        status.state, status.state, escape(&status.detail.join(" / ")),
+        escape(&status.tr_number), escape(&status.specimen),
+        timeline_html(status),
+        report::format_duration(chrono::Duration::seconds(status.kpi.test_seconds)),
+        report::format_duration(chrono::Duration::seconds(status.kpi.loss_seconds)),
+        status.kpi.utilization, losses)
+}
+
+/// Dashboard page with all stands
+pub fn dashboard_html(stands: &[Result<StandStatus, String>]) -> String {
+    let mut body = String::new();
+    for stand in stands {
+        match stand {
+            Ok(status) => body.push_str(&stand_html(status)),
+            Err(e) => body.push_str(&format!("<section><p class=\"error\">{}</p></section>", escape(e))),
+        }
+    }
+    format!("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta http-equiv=\"refresh\" content=\"30\">\
+<title>MADL dashboard</title><style>\
+body{{font-family:sans-serif}} section{{border:1px solid #ccc;margin:1em;padding:0 1em}}\
+.timeline{{position:relative;height:1.5em;background:#eee}} .timeline div{{position:absolute;height:100%}}\
+.test{{background:#4caf50}} .loss{{background:#f44336}} .pause{{background:#ff9800}} .IN{{color:#2e7d32}} .OUT{{color:#c62828}} .PAUSE{{color:#ef6c00}} .error{{color:#c62828}}\
+</style></head><body><h1>MADL dashboard</h1>{}</body></html>", body)
+}
+
+type TextResponse = Response<Cursor<Vec<u8>>>;
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], value.as_bytes()).unwrap()
+}
+
+fn json_response(text: String) -> TextResponse {
+    Response::from_string(text).with_header(content_type("application/json"))
+}
+
+/// Token from `Authorization: Bearer <token>` header or `token` query parameter
+fn request_token(request: &Request) -> Option<String> {
+    let header = request.headers().iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer ").map(|t| t.trim().to_string()));
+    header.or_else(|| {
+        let (_, query) = request.url().split_once('?')?;
+        query.split('&').find_map(|pair| pair.strip_prefix("token=").map(|t| t.to_string()))
+    })
+}
+
+/// Response of API endpoint `/api/stands/<nm>/<path>`, `None` for other paths
+#[cfg(feature = "api")]
+fn respond_api(config: &Config, request: &mut Request, path: &str) -> Result<Option<TextResponse>, Box<dyn Error>> {
+    let (nm, path) = match path.strip_prefix("/api/stands/")
+        .and_then(|rest| rest.split_once('/'))
+        .and_then(|(nm, path)| nm.parse::<u8>().ok().map(|nm| (nm, path))) {
+        Some(endpoint) => endpoint,
+        None => return Ok(None),
+    };
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        return Ok(Some(Response::from_string(format!("Invalid request body: {}", e)).with_status_code(400)));
+    }
+    let method = request.method().to_string();
+    Ok(Some(match config.all_stands()?.iter().find(|c| c.stand_nm == nm) {
+        Some(stand) => match api::route(stand, &method, path, &body) {
+            Some(Ok(text)) => json_response(text),
+            Some(Err(e)) => Response::from_string(e.message).with_status_code(e.status),
+            None => Response::from_string("Not found").with_status_code(404),
+        },
+        None => Response::from_string("Stand not found").with_status_code(404),
+    }))
+}
+
+/// Response to request of dashboard or status `path`
+fn respond(config: &Config, path: &str) -> Result<TextResponse, Box<dyn Error>> {
+    Ok(match path {
+        "" => Response::from_string(dashboard_html(&all_status(config)?)).with_header(content_type("text/html; charset=utf-8")),
+        #[cfg(feature = "metrics")]
+        "/metrics" => Response::from_string(metrics::metrics(config)?).with_header(content_type(metrics::CONTENT_TYPE)),
+        "/api/stands" => {
+            let stands: Vec<StandStatus> = all_status(config)?.into_iter().filter_map(|s| s.ok()).collect();
+            json_response(serde_json::to_string(&stands)?)
+        },
+        _ => match path.strip_prefix("/api/stands/").and_then(|nm| nm.parse::<u8>().ok()) {
+            Some(nm) => match config.all_stands()?.iter().find(|c| c.stand_nm == nm) {
+                Some(stand) => json_response(serde_json::to_string(&report::stand_status(stand)?)?),
+                None => Response::from_string("Stand not found").with_status_code(404),
+            },
+            None => Response::from_string("Not found").with_status_code(404),
+        },
+    })
+}
+
+/// Serve dashboard page on `/` and JSON API on `/api/stands` and `/api/stands/<nm>` on address `bind`.
+/// With `token` every request needs it in `Authorization: Bearer` header or `token` query parameter.
+pub fn serve(config: &Config, bind: &str, port: u16, token: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let server = Server::http((bind, port)).map_err(|e| e.to_string())?;
+    println!("Dashboard running on http://{}:{}/", bind, port);
+
+    for request in server.incoming_requests() {
+        // Body of API request is read
+        #[cfg(feature = "api")]
+        let mut request = request;
+        let url = request.url().to_string();
+        let path = url.split('?').next().unwrap_or_default().trim_end_matches('/');
+        let response = if token.is_some_and(|token| request_token(&request).as_deref() != Some(token)) {
+            Response::from_string("Unauthorized").with_status_code(401)
+        } else {
+            #[cfg(feature = "api")]
+            let result = respond_api(config, &mut request, path).transpose().unwrap_or_else(|| respond(config, path));
+            #[cfg(not(feature = "api"))]
+            let result = respond(config, path);
+            // Error of one request is reported to client, server keeps running
+            result.unwrap_or_else(|e| {
+                eprintln!("Request {} {} failed: {}", request.method(), path, e);
+                Response::from_string(format!("Internal error: {}", e)).with_status_code(500)
+            })
+        };
+        if let Err(e) = request.respond(response) {
+            eprintln!("Response not sent: {}", e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,541 @@
+//! Test workflow shared by console and terminal UI, operator inputs are read by `Prompt`
+use std::error::Error;
+use std::io;
+use std::io::prelude::*;
+
+use crate::{Config, DefFile, Laststate, Specimen, TcState, TestDefinition, TestInfo, TestCategory, TestLossClass,
+    StopReason, TestStopReasons, Interlock, read_last_state, check_state, read_index, update_interlock,
+    write_log_line, write_test_definition, write_test_start, write_missing_test_end, write_test_loss_end,
+    write_test_loss, write_pause, write_resume, write_test_end, fields, lang, registry};
+use crate::lang::Answer;
+
+/// Inputs of operator and messages for operator
+pub trait Prompt {
+    /// Index of value chosen from list, `None` if cancelled
+    fn select(&mut self, title: &str, items: &[String]) -> Result<Option<usize>, Box<dyn Error>>;
+    /// Inserted text, `None` if cancelled
+    fn input(&mut self, title: &str) -> Result<Option<String>, Box<dyn Error>>;
+    /// Answer to question from `expected`, `None` if cancelled
+    fn answer(&mut self, question: &str, expected: &[Answer]) -> Result<Option<Answer>, Box<dyn Error>>;
+    /// Confirmation of shown values
+    fn confirm(&mut self, title: &str, lines: &[String]) -> Result<bool, Box<dyn Error>>;
+    fn message(&mut self, text: &str);
+}
+
+/// Prompt reading standard input
+pub struct Console;
+
+impl Console {
+    /// Line inserted after `prompt`, `None` at end of input
+    fn read_line(&self, prompt: &str) -> Result<Option<String>, Box<dyn Error>> {
+        print!("{}", prompt);
+        let mut str_input = String::new();
+        io::stdout().flush()?;
+        if io::stdin().read_line(&mut str_input)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(str_input.trim().to_string()))
+    }
+}
+
+impl Prompt for Console {
+    fn select(&mut self, title: &str, items: &[String]) -> Result<Option<usize>, Box<dyn Error>> {
+        println!("\n{}", title);
+        for (i, item) in items.iter().enumerate() {
+            println!("{}. {}", i, item);
+        }
+        Ok(Some(read_index(items.len())?))
+    }
+
+    fn input(&mut self, title: &str) -> Result<Option<String>, Box<dyn Error>> {
+        println!("\n{}", title);
+        self.read_line(">>")
+    }
+
+    fn answer(&mut self, question: &str, expected: &[Answer]) -> Result<Option<Answer>, Box<dyn Error>> {
+        loop {
+            let input = match self.read_line(&format!("\n{}\n>>", question))? {
+                Some(input) => input,
+                None => return Ok(None),
+            };
+            match lang::answer(&input, expected) {
+                Some(answer) => return Ok(Some(answer)),
+                None => println!("{}", lang::text("wrong_input")),
+            }
+        }
+    }
+
+    fn confirm(&mut self, title: &str, lines: &[String]) -> Result<bool, Box<dyn Error>> {
+        println!("\n{}", title);
+        for line in lines {
+            println!("{}", line);
+        }
+        loop {
+            let input = match self.read_line(&format!("\n{}", lang::text("confirm_data")))? {
+                Some(input) => input,
+                None => return Ok(false),
+            };
+            match lang::answer(&input, &[Answer::Yes, Answer::No]) {
+                Some(answer) => return Ok(answer == Answer::Yes),
+                None => println!("{}", lang::text("wrong_input")),
+            }
+        }
+    }
+
+    fn message(&mut self, text: &str) {
+        println!("\n{}", text);
+    }
+}
+
+/// Values of definition with labels
+pub fn definition_lines(definition: &TestDefinition) -> Vec<String> {
+    vec!(
+        format!("{}: {}", lang::text("label_tr_number"), definition.tr_number),
+        format!("{}: {}", lang::text("label_specimen_id"), definition.specimen_id),
+        format!("{}: {}", lang::text("label_request_type"), definition.request_type),
+        format!("{}: {}", lang::text("label_test_category"), definition.testing_category),
+        format!("{}: {}", lang::text("label_operator"), definition.technician),
+    ).into_iter().chain(definition.fields.iter().map(|(name, value)| format!("{}: {}", name, value))).collect()
+}
+
+/// Choose value from one line config file
+fn select_info(prompt: &mut dyn Prompt, title: &str, config: &Config, cfg: &std::path::PathBuf) -> Result<Option<String>, Box<dyn Error>> {
+    let info = TestInfo::new(&config.get_config_file_path(cfg))?;
+    let items: Vec<String> = info.values.iter().map(|v| v.trim().to_string()).collect();
+    Ok(prompt.select(title, &items)?.map(|i| items[i].to_owned()))
+}
+
+/// Value of custom field, asked again until it is valid
+fn field_input(prompt: &mut dyn Prompt, config: &Config, field: &fields::DefinitionField) -> Result<Option<String>, Box<dyn Error>> {
+    let question = match &field.field_type {
+        fields::FieldType::List(_) => lang::format("choose_field", &[&field.label()]),
+        _ => lang::format("write_field", &[&field.label()]),
+    };
+    let mut title = question.clone();
+    loop {
+        let value = match &field.field_type {
+            fields::FieldType::List(cfg) => select_info(prompt, &title, config, cfg)?,
+            _ => prompt.input(&title)?,
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match field.check(&value, config) {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => title = format!("{} {}", lang::format("insert_again", &[&e]), question),
+        }
+    }
+}
+
+/// Ask for test definition and keep it in temporary file until start of test, `false` if cancelled
+pub fn define_test(config: &Config, prompt: &mut dyn Prompt) -> Result<bool, Box<dyn Error>> {
+    let state = read_last_state(config)?;
+    let deffile = DefFile::new(config);
+    let mut definition = deffile.read_temp_output(state.definition)?;
+    if prompt.confirm(&lang::text("use_previous_values"), &definition_lines(&definition))? {
+        deffile.write_temp_output(&definition)?;
+        prompt.message(&lang::format("test_defined", &[&definition.tr_number]));
+        return Ok(true);
+    }
+
+    loop {
+        let mut warnings = Vec::new();
+        let tr = match prompt.input(&lang::text("write_tr_number"))? {
+            Some(tr) => tr,
+            None => return Ok(false),
+        };
+        let tr_registry = registry::TrRegistry::new(&config.get_config_file_path(&config.tr_registry_cfg))?;
+        let tr_entry = match tr_registry.find(&tr) {
+            Some(entry) => Some(entry.clone()),
+            None => {
+                let found = tr_registry.search(&tr);
+                if found.is_empty() {
+                    None
+                } else {
+                    let mut items: Vec<String> = found.iter()
+                        .map(|e| format!("{} ({}, {})", e.tr_number, e.customer, e.project))
+                        .collect();
+                    items.push(lang::format("tr_keep", &[&tr]));
+                    match prompt.select(&lang::format("tr_did_you_mean", &[&tr]), &items)? {
+                        Some(num) if num < found.len() => Some(found[num].clone()),
+                        Some(_) => None,
+                        None => return Ok(false),
+                    }
+                }
+            },
+        };
+        match &tr_entry {
+            Some(entry) => {
+                prompt.message(&lang::format("tr_entry", &[&entry.tr_number, &entry.customer, &entry.project]));
+                if entry.is_closed() {
+                    warnings.push(lang::format("tr_closed", &[&entry.tr_number]));
+                }
+                definition.tr_number = entry.tr_number.to_owned();
+            },
+            None => {
+                warnings.push(lang::format("tr_not_in_registry", &[&tr]));
+                definition.tr_number = tr;
+            },
+        }
+
+        let specimens = match prompt.input(&lang::text("write_specimen_id"))? {
+            Some(specimen) => Specimen::parse_list(&specimen),
+            None => return Ok(false),
+        };
+        if let Some(entry) = &tr_entry {
+            for specimen in specimens.iter().filter(|s| !entry.expects_specimen(&s.id)) {
+                warnings.push(lang::format("specimen_not_expected", &[&specimen.id, &entry.tr_number, &entry.specimens.join(", ")]));
+            }
+        }
+        definition.specimen_id = Specimen::format_list(&specimens);
+
+        match &tr_entry {
+            Some(entry) => {
+                prompt.message(&lang::format("registry_values", &[&entry.request_type, &entry.category, &entry.planned_duration]));
+                definition.request_type = entry.request_type.to_owned();
+                definition.testing_category = entry.category.to_owned();
+                definition.available_time = entry.planned_duration.to_owned();
+            },
+            None => {
+                match select_info(prompt, &lang::text("choose_request_type"), config, &config.test_request_type_cfg)? {
+                    Some(value) => definition.request_type = value,
+                    None => return Ok(false),
+                };
+                let test_category = TestCategory::new(&config.get_config_file_path(&config.test_category_cfg))?;
+                let items: Vec<String> = test_category.values.iter().map(|(k, v)| format!("{} in duration {}", k, v)).collect();
+                let (category, time) = match prompt.select(&lang::text("choose_test_category"), &items)? {
+                    Some(num) => test_category.values.iter().nth(num).unwrap(),
+                    None => return Ok(false),
+                };
+                definition.testing_category = category.to_owned();
+                definition.available_time = time.to_owned();
+            },
+        }
+
+        match select_info(prompt, &lang::text("choose_operator"), config, &config.operator_list_cfg)? {
+            Some(value) => definition.technician = value,
+            None => return Ok(false),
+        };
+        // Next prompts are in language of chosen operator
+        lang::select(config, Some(&definition.technician));
+
+        for field in config.definition_fields.iter() {
+            match field_input(prompt, config, field)? {
+                Some(value) => definition.fields.insert(field.name.to_owned(), value),
+                None => return Ok(false),
+            };
+        }
+
+        let mut lines = definition_lines(&definition);
+        lines.extend(warnings);
+        if prompt.confirm(&lang::text("check_values"), &lines)? {
+            deffile.write_temp_output(&definition)?;
+            prompt.message(&lang::format("test_defined", &[&definition.tr_number]));
+            return Ok(true);
+        }
+    }
+}
+
+/// Time loss classification chosen from three levels, `None` if cancelled
+pub fn choose_time_loss(config: &Config, prompt: &mut dyn Prompt) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    let loss_class = TestLossClass::new(&config.get_config_file_path(&config.timeloss_classification_cfg))?;
+    loop {
+        let first: Vec<String> = loss_class.values.keys().cloned().collect();
+        let first = match prompt.select(&lang::text("choose_time_loss"), &first)? {
+            Some(num) => &first[num],
+            None => return Ok(None),
+        };
+        let second_level = &loss_class.values[first];
+        let second: Vec<String> = second_level.keys().cloned().collect();
+        let second = match prompt.select(&lang::text("choose_sub_classification"), &second)? {
+            Some(num) => &second[num],
+            None => return Ok(None),
+        };
+        let third_level = &second_level[second];
+        let third = match prompt.select(&lang::text("choose_sub_classification"), third_level)? {
+            Some(num) => &third_level[num],
+            None => return Ok(None),
+        };
+
+        let out = vec!(first.to_owned(), second.to_owned(), third.to_owned());
+        let lines = vec!(
+            format!("{}: {}", lang::text("label_time_loss_classification"), out[0]),
+            format!("{}: {}", lang::text("label_time_loss_sub_classification"), out[1]),
+            format!("{}: {}", lang::text("label_time_loss_sub_category"), out[2]),
+        );
+        if prompt.confirm(&lang::text("check_values"), &lines)? {
+            return Ok(Some(out));
+        }
+    }
+}
+
+/// Stop reason with sub-reason, `None` if cancelled
+fn choose_stop_reason(config: &Config, prompt: &mut dyn Prompt) -> Result<Option<StopReason>, Box<dyn Error>> {
+    let stop_reasons = TestStopReasons::new(&config.get_config_file_path(&config.test_stop_reason_list_cfg))?;
+    let reasons = stop_reasons.reasons();
+    let entries = match prompt.select(&lang::text("choose_test_end_reason"), &reasons)? {
+        Some(i) => stop_reasons.entries(&reasons[i]),
+        None => return Ok(None),
+    };
+    if entries.len() == 1 {
+        return Ok(Some(entries[0].clone()));
+    }
+    let items: Vec<String> = entries.iter().map(|e| e.sub_reason.clone().unwrap_or_else(|| "none".to_string())).collect();
+    Ok(prompt.select(&lang::text("choose_sub_reason"), &items)?.map(|i| entries[i].clone()))
+}
+
+/// Optional free text comment of record
+fn comment(prompt: &mut dyn Prompt) -> Result<Option<String>, Box<dyn Error>> {
+    Ok(prompt.input(&lang::text("write_comment"))?.filter(|c| !c.is_empty()))
+}
+
+/// Optional reason of pause from time loss classification
+fn pause_reason(config: &Config, prompt: &mut dyn Prompt) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    match prompt.answer(&lang::text("classify_pause"), &[Answer::Yes, Answer::No])? {
+        Some(Answer::Yes) => choose_time_loss(config, prompt),
+        _ => Ok(None),
+    }
+}
+
+/// Ask for end or pause of running test and write it with following time loss,
+/// `Some(true)` if test was paused, `None` if cancelled
+fn end_test(config: &Config, prompt: &mut dyn Prompt) -> Result<Option<bool>, Box<dyn Error>> {
+    match prompt.answer(&lang::text("end_or_pause"), &[Answer::End, Answer::Pause])? {
+        Some(Answer::Pause) => {
+            let reason = pause_reason(config, prompt)?;
+            write_pause(config, reason)?;
+            Ok(Some(true))
+        },
+        Some(_) => {
+            let reason = match choose_stop_reason(config, prompt)? {
+                Some(reason) => reason,
+                None => return Ok(None),
+            };
+            let end_comment = comment(prompt)?;
+            write_test_end(config, &reason, end_comment.as_deref())?;
+            if let Some(loss) = choose_time_loss(config, prompt)? {
+                let loss_comment = comment(prompt)?;
+                write_test_loss(config, loss, loss_comment.as_deref())?;
+            }
+            Ok(Some(false))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Pause running test, returns false if no test is running
+pub fn pause_test(config: &Config, prompt: &mut dyn Prompt) -> Result<bool, Box<dyn Error>> {
+    match check_state(&read_last_state(config)?) {
+        Laststate::IN(vec_data) if vec_data[0].contains("Test Start") => (),
+        _ => return Ok(false),
+    }
+    let reason = pause_reason(config, prompt)?;
+    write_pause(config, reason)?;
+    Ok(true)
+}
+
+/// Change time loss classification, running test is ended or paused first
+pub fn change_timeloss(config: &Config, prompt: &mut dyn Prompt) -> Result<(), Box<dyn Error>> {
+    match check_state(&read_last_state(config)?) {
+        Laststate::IN(vec_data) if vec_data[0].contains("Test Start") => {
+            if end_test(config, prompt)?.is_none() {
+                prompt.message(&lang::text("end_not_recorded"));
+            }
+            return Ok(());
+        },
+        Laststate::PAUSE(_) => {
+            prompt.message(&lang::text("pause_reason_change"));
+            if let Some(loss) = choose_time_loss(config, prompt)? {
+                write_pause(config, Some(loss))?;
+                prompt.message(&lang::text("pause_reason_changed"));
+            }
+            return Ok(());
+        },
+        Laststate::IN(vec_data) => {
+            let loss = match choose_time_loss(config, prompt)? {
+                Some(loss) => loss,
+                None => return Ok(()),
+            };
+            let loss_comment = comment(prompt)?;
+            write_test_loss_end(config, &vec_data)?;
+            write_test_loss(config, loss, loss_comment.as_deref())?;
+        },
+        Laststate::OUT(_) | Laststate::EMPTY => {
+            let loss = match choose_time_loss(config, prompt)? {
+                Some(loss) => loss,
+                None => return Ok(()),
+            };
+            let loss_comment = comment(prompt)?;
+            write_test_loss(config, loss, loss_comment.as_deref())?;
+        },
+    }
+    prompt.message(&lang::text("time_loss_changed"));
+    Ok(())
+}
+
+/// Check interlock before start of test, disabled interlock can be overridden with reason
+pub fn interlock_allows_start(config: &Config, prompt: &mut dyn Prompt) -> Result<bool, Box<dyn Error>> {
+    let (state, changed) = update_interlock(config)?;
+    if changed {
+        prompt.message(&lang::format("interlock_changed", &[&state]));
+    }
+    if state == Interlock::Enabled {
+        return Ok(true);
+    }
+    prompt.message(&lang::text("interlock_disabled"));
+    match prompt.input(&lang::text("write_override_reason"))? {
+        Some(reason) if !reason.is_empty() => {
+            write_log_line(config, vec!("INTERLOCK", "Override", &reason))?;
+            Ok(true)
+        },
+        _ => Ok(false),
+    }
+}
+
+/// Write records for state read from TC log, returns false if measurement ended
+pub fn handle_tc(config: &Config, prompt: &mut dyn Prompt, received: TcState) -> Result<bool, Box<dyn Error>> {
+    let state = read_last_state(config)?;
+    let last_state = check_state(&state);
+
+    match received {
+        TcState::Start(_) => {
+            if !interlock_allows_start(config, prompt)? {
+                prompt.message(&lang::text("interlock_blocks_start"));
+                return Ok(true);
+            }
+            // Paused test continues without new definition
+            if let Laststate::PAUSE(_) = last_state {
+                write_resume(config)?;
+                prompt.message(&lang::text("measurement_resumed"));
+                return Ok(true);
+            }
+            // Definition can be written by API while waiting for start
+            let deffile = DefFile::new(config);
+            let definition = deffile.read_temp_output(state.definition)?;
+            lang::select(config, Some(&definition.technician));
+            match last_state {
+                Laststate::IN(ref vec_data) if vec_data[0].contains("Test Start") => {
+                    prompt.message(&lang::text("last_log_from_start"));
+                    write_missing_test_end(config)?;
+                },
+                Laststate::IN(ref vec_data) => write_test_loss_end(config, vec_data)?,
+                _ => (),
+            }
+            write_test_definition(config, &definition)?;
+            write_test_start(config)?;
+            deffile.remove_temp_file()?;
+            prompt.message(&lang::text("measurement_started"));
+            Ok(true)
+        },
+        TcState::End(_) => {
+            match last_state {
+                Laststate::IN(ref vec_data) if vec_data[0].contains("Test Start") => {
+                    match end_test(config, prompt)? {
+                        Some(true) => {
+                            prompt.message(&lang::text("paused_until_start"));
+                            return Ok(true);
+                        },
+                        Some(false) => (),
+                        None => {
+                            prompt.message(&lang::text("end_not_recorded"));
+                            return Ok(true);
+                        },
+                    }
+                },
+                Laststate::IN(ref vec_data) => write_test_loss_end(config, vec_data)?,
+                Laststate::OUT(ref vec_data) => {
+                    prompt.message(&lang::format("already_stopped", &[&vec_data[0], &vec_data[1], &vec_data[2]]));
+                    return Ok(true);
+                },
+                Laststate::PAUSE(_) => {
+                    prompt.message(&lang::text("test_already_paused"));
+                    return Ok(true);
+                },
+                Laststate::EMPTY => {
+                    prompt.message(&lang::text("no_previous_record"));
+                    return Ok(true);
+                },
+            }
+            prompt.message(&lang::text("measurement_end"));
+            Ok(false)
+        },
+        TcState::Empty => {
+            prompt.message(&lang::text("empty_tc_line"));
+            Ok(false)
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::fs;
+    use crate::tests::temp_stand;
+
+    /// Prompt with prepared inputs, `None` cancels the dialog
+    #[derive(Default)]
+    struct Script {
+        inputs: VecDeque<Option<String>>,
+        messages: Vec<String>,
+    }
+
+    impl Script {
+        fn next(&mut self) -> Option<String> {
+            self.inputs.pop_front().expect("no prepared input")
+        }
+    }
+
+    impl Prompt for Script {
+        fn select(&mut self, _title: &str, _items: &[String]) -> Result<Option<usize>, Box<dyn Error>> {
+            Ok(self.next().map(|i| i.parse().unwrap()))
+        }
+
+        fn input(&mut self, _title: &str) -> Result<Option<String>, Box<dyn Error>> {
+            Ok(self.next())
+        }
+
+        fn answer(&mut self, _question: &str, expected: &[Answer]) -> Result<Option<Answer>, Box<dyn Error>> {
+            Ok(self.next().and_then(|a| lang::answer(&a, expected)))
+        }
+
+        fn confirm(&mut self, _title: &str, _lines: &[String]) -> Result<bool, Box<dyn Error>> {
+            Ok(self.next().is_some())
+        }
+
+        fn message(&mut self, text: &str) {
+            self.messages.push(text.to_string());
+        }
+    }
+
+    fn script(inputs: &[Option<&str>]) -> Script {
+        Script { inputs: inputs.iter().map(|i| i.map(String::from)).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_handle_tc() {
+        let config = temp_stand("workflow");
+        let mut prompt = script(&[]);
+        assert!(handle_tc(&config, &mut prompt, TcState::Start(String::new())).unwrap());
+        assert!(matches!(check_state(&read_last_state(&config).unwrap()), Laststate::IN(ref d) if d[0] == "Test Start"));
+
+        // Cancelled end of test keeps test running
+        let mut prompt = script(&[Some("end"), None]);
+        assert!(handle_tc(&config, &mut prompt, TcState::End(String::new())).unwrap());
+        assert_eq!(prompt.messages, vec!(lang::text("end_not_recorded")));
+        assert!(matches!(check_state(&read_last_state(&config).unwrap()), Laststate::IN(ref d) if d[0] == "Test Start"));
+
+        // End reason, its sub-reason and comment, then time loss with its confirmation and comment
+        let mut prompt = script(&[Some("end"), Some("0"), Some("0"), Some("done"), Some("0"), Some("0"), Some("0"), Some("y"), Some("")]);
+        assert!(!handle_tc(&config, &mut prompt, TcState::End(String::new())).unwrap());
+        let state = read_last_state(&config).unwrap();
+        fs::remove_dir_all(&config.settings_dir).unwrap();
+        assert!(prompt.inputs.is_empty());
+        match check_state(&state) {
+            Laststate::IN(fields) => assert_eq!(fields[0], "Idle Time"),
+            _ => panic!("Time loss is not running: {}", state.last_line),
+        }
+    }
+}